@@ -0,0 +1,116 @@
+//! Integration and broadphase bounds over a list of whole circles compared with the
+//! structure of arrays kept by the world.
+//! Run with: cargo bench --bench layout
+
+use circle_collision::balls::Circle;
+use circle_collision::grfx::color;
+use circle_collision::math::vector::FVec2D;
+use circle_collision::physics::bodies::Bodies;
+use circle_collision::physics::integrator::Integrator;
+use circle_collision::physics::query::Aabb;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::Instant;
+
+const CIRCLES: usize = 100_000;
+const ROUNDS: usize = 200;
+const SIZE: f32 = 4000.0;
+const DELTA_T: f32 = 1.0 / 240.0;
+
+fn circles() -> Vec<Circle> {
+    let mut rand = StdRng::seed_from_u64(32);
+    (0..CIRCLES)
+        .map(|_| {
+            let mut circle = Circle::new(
+                FVec2D::new(rand.gen_range(0.0..SIZE), rand.gen_range(0.0..SIZE)),
+                rand.gen_range(3.0..6.0),
+                FVec2D::new(rand.gen_range(-100.0..100.0), rand.gen_range(-100.0..100.0)),
+                color::RED,
+            );
+            circle.sim_time_remaining = DELTA_T;
+            circle
+        })
+        .collect()
+}
+
+// The integration loop as it was written over a list of circles
+fn integrate_circles(circles: &mut [Circle], gravity: FVec2D, drag: f32) {
+    for circle in circles.iter_mut() {
+        if circle.sim_time_remaining > 0.0 {
+            circle.prev_center = circle.center;
+            circle.acceletation = -circle.speed * drag + gravity;
+            circle.speed += circle.acceletation * circle.sim_time_remaining;
+            circle.center += circle.speed * circle.sim_time_remaining;
+            if circle.center.x < 0.0 {
+                circle.center.x += SIZE;
+            }
+            if circle.center.y < 0.0 {
+                circle.center.y += SIZE;
+            }
+            if circle.center.x > SIZE {
+                circle.center.x -= SIZE;
+            }
+            if circle.center.y > SIZE {
+                circle.center.y -= SIZE;
+            }
+            if circle.speed.length() <= 0.01 {
+                circle.speed = FVec2D::new(0.0, 0.0);
+            }
+        }
+    }
+}
+
+fn bounds_circles(circles: &[Circle]) -> Vec<Aabb> {
+    circles
+        .iter()
+        .map(|c| Aabb::from_circle(c).expanded(c.speed.length() * DELTA_T))
+        .collect()
+}
+
+// Milliseconds per round
+fn time<F: FnMut()>(mut round: F) -> f64 {
+    round();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        round();
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / ROUNDS as f64
+}
+
+fn report(name: &str, circles: f64, bodies: f64) {
+    println!(
+        "{:<10} circles {:>7.3} ms  bodies {:>7.3} ms  {:>5.2}x",
+        name,
+        circles,
+        bodies,
+        circles / bodies
+    );
+}
+
+fn main() {
+    println!("{} circles, single thread", CIRCLES);
+    let gravity = FVec2D::new(0.0, 100.0);
+    let mut list = circles();
+    let mut bodies = Bodies::from(list.clone());
+
+    let list_time = time(|| integrate_circles(black_box(&mut list), gravity, 0.8));
+    let bodies_time = time(|| {
+        black_box(&mut bodies).integrate(
+            Integrator::SemiImplicitEuler,
+            gravity,
+            0.8,
+            Some(FVec2D::new(SIZE, SIZE)),
+            1,
+        )
+    });
+    report("integrate", list_time, bodies_time);
+
+    let list_time = time(|| {
+        black_box(bounds_circles(black_box(&list)));
+    });
+    let bodies_time = time(|| {
+        black_box(black_box(&bodies).bounds(DELTA_T));
+    });
+    report("bounds", list_time, bodies_time);
+}
@@ -0,0 +1,71 @@
+//! How stepping the world scales with the number of threads.
+//! Run with: cargo bench --bench parallel
+
+use circle_collision::balls::{Circle, LineSegment};
+use circle_collision::grfx::color;
+use circle_collision::math::vector::FVec2D;
+use circle_collision::physics::parallel;
+use circle_collision::physics::world::World;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Instant;
+
+const CIRCLES: usize = 10_000;
+const STEPS: usize = 30;
+
+// 10k small circles moving around inside a box
+fn scene(threads: usize) -> World {
+    let mut world = World::new(2000.0, 2000.0);
+    world.threads = threads;
+    let corners = [
+        FVec2D::new(20.0, 20.0),
+        FVec2D::new(20.0, 1980.0),
+        FVec2D::new(1980.0, 1980.0),
+        FVec2D::new(1980.0, 20.0),
+        FVec2D::new(20.0, 20.0),
+    ];
+    for side in corners.windows(2) {
+        world.lines.push(LineSegment::new(side[0], side[1], 5.0));
+    }
+    let mut rand = StdRng::seed_from_u64(31);
+    for _ in 0..CIRCLES {
+        world.circles.push(Circle::new(
+            FVec2D::new(rand.gen_range(40.0..1960.0), rand.gen_range(40.0..1960.0)),
+            rand.gen_range(3.0..6.0),
+            FVec2D::new(rand.gen_range(-100.0..100.0), rand.gen_range(-100.0..100.0)),
+            color::RED,
+        ));
+    }
+    world
+}
+
+fn main() {
+    let available = parallel::available_threads();
+    println!(
+        "{} circles, {} steps, {} threads available",
+        CIRCLES, STEPS, available
+    );
+    let mut threads = 1;
+    let mut single_thread = 0.0;
+    while threads <= available.max(1) {
+        let mut world = scene(threads);
+        // let the initial overlaps settle so every run measures the same kind of work
+        world.step(1.0 / 60.0);
+
+        let start = Instant::now();
+        for _ in 0..STEPS {
+            world.step(1.0 / 60.0);
+        }
+        let per_step = start.elapsed().as_secs_f64() * 1000.0 / STEPS as f64;
+        if threads == 1 {
+            single_thread = per_step;
+        }
+        println!(
+            "{:>2} threads: {:>8.2} ms/step  {:>5.2}x",
+            threads,
+            per_step,
+            single_thread / per_step
+        );
+        threads *= 2;
+    }
+}
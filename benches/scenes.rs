@@ -0,0 +1,168 @@
+//! Standard scenes stepped headlessly to catch performance regressions.
+//! Run with: cargo bench --bench scenes
+//! Pass scene names to only run some of them: cargo bench --bench scenes -- pile
+
+use circle_collision::balls::{Circle, LineSegment};
+use circle_collision::grfx::color;
+use circle_collision::math::vector::FVec2D;
+use circle_collision::physics::world::World;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::env;
+use std::time::Instant;
+
+const DELTA_T: f32 = 1.0 / 60.0;
+
+struct Scene {
+    name: &'static str,
+    build: fn() -> World,
+    // steps run before timing starts, lets piles settle
+    warmup: usize,
+    steps: usize,
+}
+
+const SCENES: [Scene; 3] = [
+    Scene {
+        name: "drawable",
+        build: drawable,
+        warmup: 60,
+        steps: 600,
+    },
+    Scene {
+        name: "box",
+        build: crowded_box,
+        warmup: 10,
+        steps: 60,
+    },
+    Scene {
+        name: "pile",
+        build: pile,
+        warmup: 300,
+        steps: 60,
+    },
+];
+
+fn add_edges(world: &mut World, corners: &[FVec2D], radius: f32) {
+    for side in corners.windows(2) {
+        world.lines.push(LineSegment::new(side[0], side[1], radius));
+    }
+}
+
+// The scene Drawable::setup builds: 101 circles dropped on four shelves
+fn drawable() -> World {
+    let mut world = World::new(800.0, 600.0);
+    let mut rand = StdRng::seed_from_u64(33);
+    for _ in 0..=100 {
+        world.circles.push(Circle::new(
+            FVec2D::new(rand.gen_range(0..800) as f32, rand.gen_range(0..600) as f32),
+            8.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+    }
+    for y in [30.0, 50.0, 80.0, 120.0].iter() {
+        add_edges(
+            &mut world,
+            &[FVec2D::new(30.0, *y), FVec2D::new(300.0, *y)],
+            10.0,
+        );
+    }
+    world
+}
+
+// 5k circles bouncing around a closed box
+fn crowded_box() -> World {
+    let mut world = World::new(1600.0, 1600.0);
+    add_edges(
+        &mut world,
+        &[
+            FVec2D::new(20.0, 20.0),
+            FVec2D::new(20.0, 1580.0),
+            FVec2D::new(1580.0, 1580.0),
+            FVec2D::new(1580.0, 20.0),
+            FVec2D::new(20.0, 20.0),
+        ],
+        5.0,
+    );
+    let mut rand = StdRng::seed_from_u64(34);
+    for _ in 0..5000 {
+        world.circles.push(Circle::new(
+            FVec2D::new(rand.gen_range(40.0..1560.0), rand.gen_range(40.0..1560.0)),
+            rand.gen_range(3.0..6.0),
+            FVec2D::new(rand.gen_range(-100.0..100.0), rand.gen_range(-100.0..100.0)),
+            color::RED,
+        ));
+    }
+    world
+}
+
+// 2k circles packed into a bucket with a sloped floor, left to settle into a pile
+fn pile() -> World {
+    let mut world = World::new(1000.0, 1000.0);
+    add_edges(
+        &mut world,
+        &[
+            FVec2D::new(200.0, 100.0),
+            FVec2D::new(200.0, 900.0),
+            FVec2D::new(500.0, 950.0),
+            FVec2D::new(800.0, 900.0),
+            FVec2D::new(800.0, 100.0),
+        ],
+        10.0,
+    );
+    let mut rand = StdRng::seed_from_u64(35);
+    for row in 0..40 {
+        for column in 0..50 {
+            world.circles.push(Circle::new(
+                FVec2D::new(
+                    225.0 + column as f32 * 11.0 + rand.gen_range(-0.5..0.5),
+                    880.0 - row as f32 * 11.0,
+                ),
+                5.0,
+                FVec2D::new(0.0, 0.0),
+                color::RED,
+            ));
+        }
+    }
+    world
+}
+
+fn main() {
+    // cargo passes --bench along, anything else picks scenes by name
+    let filters: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    println!(
+        "{:<10} {:>7} {:>12} {:>14} {:>14}",
+        "scene", "bodies", "ms/step", "pairs/step", "contacts/step"
+    );
+    for scene in SCENES.iter() {
+        if !filters.is_empty() && !filters.iter().any(|f| scene.name.contains(f.as_str())) {
+            continue;
+        }
+        let mut world = (scene.build)();
+        for _ in 0..scene.warmup {
+            world.step(DELTA_T);
+        }
+
+        let mut pairs_tested = 0;
+        let mut contacts = 0;
+        let start = Instant::now();
+        for _ in 0..scene.steps {
+            world.step(DELTA_T);
+            pairs_tested += world.stats.pairs_tested;
+            contacts += world.stats.contacts;
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        let steps = scene.steps as f64;
+        println!(
+            "{:<10} {:>7} {:>12.3} {:>14.0} {:>14.0}",
+            scene.name,
+            world.circles.len(),
+            elapsed * 1000.0 / steps,
+            pairs_tested as f64 / steps,
+            contacts as f64 / steps
+        );
+    }
+}
@@ -1,11 +1,20 @@
-use crate::grfx::canvas::Canvas;
+use crate::grfx::canvas::{Canvas, LineCap};
 use crate::grfx::color;
-use crate::grfx::color::Color;
+use crate::grfx::color::{BlendMode, Color};
+use crate::grfx::png::PngCompression;
 use crate::grfx::render::Render2D;
-use crate::math::vector::FVec2D;
-use crate::math::vector::Point2D;
+use crate::math::vector::{FVec2D, Point2D};
+use crate::physics::bodies::Bodies;
+use crate::physics::emitter::Emitter;
+use crate::physics::fluid::Fluid;
+use crate::physics::granular::Granular;
+use crate::physics::integrator::Integrator;
+use crate::physics::joint::RevoluteJoint;
+use crate::physics::query::{Body, EdgeEnd, SpatialQuery};
+use crate::physics::world::{Boundary, Event, Solver, World};
 use rand::Rng;
 use std::time::Duration;
+use winit::event::VirtualKeyCode;
 use winit_input_helper::WinitInputHelper;
 pub struct Drawable {
     title: String,
@@ -13,8 +22,68 @@ pub struct Drawable {
     height: u32,
     selected_circle: Option<usize>,
     selected_line: LineSelection,
-    circles: Vec<Circle>,
-    lines: Vec<LineSegment>,
+    show_diagnostics: bool,
+    // fade the previous frame out instead of clearing it, leaving trails behind moving circles
+    trails: bool,
+    world: World,
+    cars: Vec<Car>,
+}
+
+// Capsule chassis made of two circles locked together, with a motorized wheel under each end
+struct Car {
+    chassis: (usize, usize),
+    wheels: (usize, usize),
+}
+
+impl Car {
+    const WHEEL_SPEED: f32 = 10.0;
+
+    fn spawn(world: &mut World, position: FVec2D) -> Self {
+        let still = FVec2D::new(0.0, 0.0);
+        let axle = FVec2D::new(0.0, 10.0);
+        let length = FVec2D::new(60.0, 0.0);
+        let circles = &mut world.circles;
+        let rear = circles.push(Circle::new(position, 6.0, still, color::CYAN));
+        let front = circles.push(Circle::new(position + length, 6.0, still, color::CYAN));
+        let rear_wheel = circles.push(Circle::new(position + axle, 12.0, still, color::GRAY));
+        let front_wheel = circles.push(Circle::new(
+            position + length + axle,
+            12.0,
+            still,
+            color::GRAY,
+        ));
+
+        let middle = position + length / 2.0;
+        let chassis = RevoluteJoint::new(circles, rear, front, middle).with_limits(0.0, 0.0);
+        let rear_axle = RevoluteJoint::new(circles, rear, rear_wheel, position + axle)
+            .with_motor(0.0, 200000.0);
+        let front_axle = RevoluteJoint::new(circles, front, front_wheel, position + length + axle)
+            .with_motor(0.0, 200000.0);
+        world.joints.extend(vec![chassis, rear_axle, front_axle]);
+        Self {
+            chassis: (rear, front),
+            wheels: (rear_wheel, front_wheel),
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, circles: &Bodies) {
+        let positions = circles.positions();
+        let (rear, front) = self.chassis;
+        canvas.thick_line(
+            positions[rear],
+            positions[front],
+            12.0,
+            LineCap::Round,
+            color::CYAN,
+        );
+        // a spoke on each wheel shows it turning
+        for &wheel in [self.wheels.0, self.wheels.1].iter() {
+            let angle = circles.angles()[wheel];
+            let spoke = FVec2D::new(angle.cos(), angle.sin()) * circles.radii()[wheel];
+            let center = positions[wheel];
+            canvas.line_between(center.to_i32(), (center + spoke).to_i32(), color::BLACK);
+        }
+    }
 }
 
 impl Drawable {
@@ -25,8 +94,28 @@ impl Drawable {
             height,
             selected_circle: None,
             selected_line: LineSelection::None,
-            circles: Vec::new(),
-            lines: Vec::new(),
+            show_diagnostics: false,
+            trails: false,
+            world: World::new(width as f32, height as f32),
+            cars: Vec::new(),
+        }
+    }
+
+    // Keep the circle indices held by the scene pointing at the same circles after a despawn
+    fn despawned(&mut self, index: usize, replaced_by: Option<usize>) {
+        let moved = |k: usize| if Some(k) == replaced_by { index } else { k };
+        self.selected_circle = match self.selected_circle {
+            Some(i) if i == index => None,
+            selected => selected.map(moved),
+        };
+        self.cars.retain(|car| {
+            let (rear, front) = car.chassis;
+            let (rear_wheel, front_wheel) = car.wheels;
+            ![rear, front, rear_wheel, front_wheel].contains(&index)
+        });
+        for car in self.cars.iter_mut() {
+            car.chassis = (moved(car.chassis.0), moved(car.chassis.1));
+            car.wheels = (moved(car.wheels.0), moved(car.wheels.1));
         }
     }
 }
@@ -46,24 +135,24 @@ impl Render2D for Drawable {
                 FVec2D::new(0.0, 0.0),
                 color::RED,
             );
-            self.circles.push(circle);
+            self.world.circles.push(circle);
         }
-        self.lines.push(LineSegment::new(
+        self.world.lines.push(LineSegment::new(
             FVec2D::new(30.0, 30.0),
             FVec2D::new(300.0, 30.0),
             10.0,
         ));
-        self.lines.push(LineSegment::new(
+        self.world.lines.push(LineSegment::new(
             FVec2D::new(30.0, 50.0),
             FVec2D::new(300.0, 50.0),
             10.0,
         ));
-        self.lines.push(LineSegment::new(
+        self.world.lines.push(LineSegment::new(
             FVec2D::new(30.0, 80.0),
             FVec2D::new(300.0, 80.0),
             10.0,
         ));
-        self.lines.push(LineSegment::new(
+        self.world.lines.push(LineSegment::new(
             FVec2D::new(30.0, 120.0),
             FVec2D::new(300.0, 120.0),
             10.0,
@@ -71,56 +160,50 @@ impl Render2D for Drawable {
         return true;
     }
     fn update(&mut self, canvas: &mut Canvas, input: &WinitInputHelper, delta_t: Duration) -> bool {
-        canvas.clear(color::BLACK);
-
-        let mut colliding_circles = Vec::<(usize, usize, bool)>::new();
-        let mut fake_balls = Vec::<Circle>::new();
+        if self.trails {
+            let (width, height) = (self.width as i32, self.height as i32);
+            canvas.with_blend_mode(BlendMode::SourceOver, |canvas| {
+                canvas.filled_rectangle(Point2D::new(0, 0), width, height, Color::rgba(0, 0, 0, 40))
+            });
+        } else {
+            canvas.clear(color::BLACK);
+        }
 
         let width = self.width() as f32;
         let height = self.height() as f32;
 
-        let circles = &mut self.circles;
         if input.mouse_pressed(0) || input.mouse_pressed(1) {
             self.selected_line = LineSelection::None;
             self.selected_circle = None;
-            for (i, circle) in circles.iter_mut().enumerate() {
-                if let Some((x, y)) = input.mouse() {
-                    let clicked_point = FVec2D::new(x, y).to_i32();
-                    if circle.hits(clicked_point, circle.radius) {
+            if let Some((x, y)) = input.mouse() {
+                let clicked_point = FVec2D::new(x, y);
+                let query = SpatialQuery::new(&self.world.circles, &self.world.lines);
+                for body in query.point(clicked_point) {
+                    if let Body::Circle(i) = body {
                         self.selected_circle = Some(i);
                     }
                 }
-            }
-
-            for (i, line) in self.lines.iter_mut().enumerate() {
-                if let Some((x, y)) = input.mouse() {
-                    let clicked_point = FVec2D::new(x, y).to_i32();
-                    let circle =
-                        Circle::new(line.start, line.radius, FVec2D::new(0.0, 0.0), color::WHITE);
-                    if circle.hits(clicked_point, circle.radius) {
-                        self.selected_line = LineSelection::Head(i);
-                    }
-                    let circle =
-                        Circle::new(line.end, line.radius, FVec2D::new(0.0, 0.0), color::WHITE);
-                    if circle.hits(clicked_point, circle.radius) {
-                        self.selected_line = LineSelection::Tail(i);
-                    }
+                match query.edge_end(clicked_point) {
+                    Some((i, EdgeEnd::Start)) => self.selected_line = LineSelection::Head(i),
+                    Some((i, EdgeEnd::End)) => self.selected_line = LineSelection::Tail(i),
+                    None => {}
                 }
             }
         }
 
+        let circles = &mut self.world.circles;
         // drag with left click
         if input.mouse_held(0) {
             if let Some((x, y)) = input.mouse() {
                 let selected_point = FVec2D::new(x, y);
                 if let Some(index) = self.selected_circle {
-                    circles[index].center = selected_point;
+                    *circles.circle_mut(index).center = selected_point;
                 }
                 if let LineSelection::Head(i) = self.selected_line {
-                    self.lines[i].start = selected_point;
+                    self.world.lines[i].start = selected_point;
                 }
                 if let LineSelection::Tail(i) = self.selected_line {
-                    self.lines[i].end = selected_point;
+                    self.world.lines[i].end = selected_point;
                 }
             }
         }
@@ -130,9 +213,21 @@ impl Render2D for Drawable {
                     let selected_point = FVec2D::new(x, y).to_i32();
                     canvas.line_between(
                         selected_point,
-                        circles[index].center.to_i32(),
+                        circles.get(index).center.to_i32(),
                         color::BLUE,
                     );
+
+                    // preview where the circle would first touch something if pushed
+                    let query = SpatialQuery::new(circles, &self.world.lines);
+                    let circle = circles.get(index);
+                    let direction = circle.center - selected_point.to_f32();
+                    let max_distance = (width * width + height * height).sqrt();
+                    if let Some(hit) =
+                        query.circle_cast(circle.center, circle.radius, direction, max_distance)
+                    {
+                        let contact = circle.center + direction.unit_vector() * hit.distance;
+                        canvas.circle(contact.to_i32(), circle.radius as i32, color::GRAY);
+                    }
                 }
             }
         }
@@ -146,167 +241,225 @@ impl Render2D for Drawable {
             if let Some(index) = self.selected_circle {
                 if let Some((x, y)) = input.mouse() {
                     let selected_point = FVec2D::new(x.abs(), y);
-                    circles[index].speed = (circles[index].center - selected_point) * 5.0;
+                    let circle = circles.circle_mut(index);
+                    *circle.speed = (*circle.center - selected_point) * 5.0;
                 }
             }
         }
-        let simulation_updates = 4;
-        let max_simulation_steps = 15;
-        let sim_elapsed_time = delta_t.as_secs_f32() / simulation_updates as f32;
-        for _ in 0..simulation_updates {
-            for circle in circles.iter_mut() {
-                circle.sim_time_remaining = sim_elapsed_time;
+        // pour water with W
+        if input.key_held(VirtualKeyCode::W) {
+            if let Some((x, y)) = input.mouse() {
+                let spacing = Fluid::PARTICLE_SPACING;
+                let corner = FVec2D::new(x - spacing, y - spacing);
+                self.world
+                    .fluid
+                    .fill(corner, corner + FVec2D::new(spacing * 2.0, spacing * 2.0));
             }
-            for _ in 0..max_simulation_steps {
-                for (_i, circle) in circles.iter_mut().enumerate() {
-                    if circle.sim_time_remaining > 0.0 {
-                        // cache current center
-                        circle.prev_center = circle.center;
-
-                        circle.acceletation = -circle.speed * 0.8 + FVec2D::new(0.0, 100.0); // drag force + gravity
-                        circle.speed += circle.acceletation * circle.sim_time_remaining;
-                        circle.center += circle.speed * circle.sim_time_remaining;
-                        if circle.center.x < 0.0 {
-                            circle.center.x += width;
-                        }
-                        if circle.center.y < 0.0 {
-                            circle.center.y += height;
-                        }
-                        if circle.center.x > width {
-                            circle.center.x -= width;
-                        }
-                        if circle.center.y > height {
-                            circle.center.y -= height;
-                        }
-                        if circle.speed.length() <= 0.01 {
-                            circle.speed = FVec2D::new(0.0, 0.0);
-                        }
+        }
+        // drop a block of welded circles with K, it shatters when it lands hard enough
+        if input.key_pressed(VirtualKeyCode::K) {
+            if let Some((x, y)) = input.mouse() {
+                let mut circles = Vec::new();
+                for row in 0..4 {
+                    for column in 0..4 {
+                        let offset = FVec2D::new(column as f32, row as f32) * 12.0;
+                        circles.push(Circle::new(
+                            FVec2D::new(x, y) + offset,
+                            6.0,
+                            FVec2D::new(0.0, 0.0),
+                            color::YELLOW,
+                        ));
                     }
                 }
-                // check for static collisions
-                for i in 0..circles.len() {
-                    // check collisions with edges
-                    for edge in self.lines.iter_mut() {
-                        let line_segment = edge.end - edge.start;
-                        let edge_to_circle_vec = circles[i].center - edge.start;
-                        let segment_length = line_segment.squared_length();
-
-                        let t = segment_length.min(FVec2D::dot(edge_to_circle_vec, line_segment))
-                            / segment_length;
-                        let t = t.max(0.0);
-
-                        let closest_point = edge.start + line_segment * t;
-
-                        let distance = (circles[i].center - closest_point).length();
-
-                        // colliding with edge
-                        if distance <= circles[i].radius + edge.radius {
-                            let mut fake_circle = Circle::new(
-                                closest_point,
-                                edge.radius,
-                                -circles[i].speed,
-                                circles[i].color,
-                            );
-
-                            //ereduce the mass a little
-                            fake_circle.mass *= 0.8;
-                            fake_balls.push(fake_circle);
-                            colliding_circles.push((i, fake_balls.len() - 1, true)); //  hack store fake circles indexes
-
-                            let overlap = distance - circles[i].radius - fake_circle.radius;
-                            let circle_center = circles[i].center;
-                            circles[i].center -=
-                                (circle_center - fake_circle.center).unit_vector() * overlap;
-                        }
-                    }
-                    for j in 0..circles.len() {
-                        if i != j {
-                            // make sure circles don't run into each other
-                            if circles_overlap(&circles[i], &circles[j]) {
-                                let distance_vec = (circles[i].center - circles[j].center).to_f32();
-                                let overlap = 0.5
-                                    * (distance_vec.length()
-                                        - circles[i].radius
-                                        - circles[j].radius);
-                                circles[i].center -= distance_vec.unit_vector() * overlap;
-                                circles[j].center += distance_vec.unit_vector() * overlap;
-                                colliding_circles.push((i, j, false));
-                            }
-                        }
+                self.world.add_cluster(circles, 100000.0);
+                if self.world.solver != Solver::Xpbd {
+                    self.world.solver = Solver::Xpbd;
+                    println!(
+                        "Solver: {:?}, clusters only hold together with it",
+                        self.world.solver
+                    );
+                }
+            }
+        }
+        // drop a car with V, the first one comes with hills along the bottom to drive on
+        if input.key_pressed(VirtualKeyCode::V) {
+            if let Some((x, y)) = input.mouse() {
+                if self.cars.is_empty() {
+                    let ground = height - 40.0;
+                    let hills = 12;
+                    for k in 0..hills {
+                        let x_at = |k: i32| width * k as f32 / hills as f32;
+                        let y_at = |k: i32| ground - 25.0 * (k as f32 * 1.3).sin().abs();
+                        self.world.lines.push(LineSegment::new(
+                            FVec2D::new(x_at(k), y_at(k)),
+                            FVec2D::new(x_at(k + 1), y_at(k + 1)),
+                            5.0,
+                        ));
                     }
-                    let intended_speed = circles[i].speed.length();
-                    let _intended_distance = intended_speed * circles[i].sim_time_remaining;
-                    let actual_distance = (circles[i].center - circles[i].prev_center).length();
-                    let actual_time = actual_distance / intended_speed;
+                }
+                self.cars
+                    .push(Car::spawn(&mut self.world, FVec2D::new(x, y)));
+                if self.world.friction == 0.0 {
+                    self.world.friction = 0.9;
+                }
+                if self.world.solver != Solver::Xpbd {
+                    self.world.solver = Solver::Xpbd;
+                    println!("Solver: {:?}, joints only hold with it", self.world.solver);
+                }
+            }
+        }
+        // drive the cars with the arrow keys, the motors brake when no key is held
+        let mut wheel_speed = 0.0;
+        if input.key_held(VirtualKeyCode::Right) {
+            wheel_speed += Car::WHEEL_SPEED;
+        }
+        if input.key_held(VirtualKeyCode::Left) {
+            wheel_speed -= Car::WHEEL_SPEED;
+        }
+        for joint in self.world.joints.iter_mut() {
+            if let Some(motor) = joint.motor.as_mut() {
+                motor.target_speed = wheel_speed;
+            }
+        }
 
-                    circles[i].sim_time_remaining -= actual_time;
+        // spawn a circle at the mouse with N and despawn the circle under the mouse with X
+        if let Some((x, y)) = input.mouse() {
+            let mouse = FVec2D::new(x, y);
+            if input.key_pressed(VirtualKeyCode::N) {
+                let mut rand = rand::thread_rng();
+                let speed = FVec2D::new(rand.gen_range(-50.0..50.0), rand.gen_range(-50.0..50.0));
+                self.world.spawn(Circle::new(mouse, 8.0, speed, color::RED));
+            }
+            if input.key_pressed(VirtualKeyCode::X) {
+                let query = SpatialQuery::new(&self.world.circles, &self.world.lines);
+                let under_mouse = query.point(mouse).into_iter().find_map(|body| match body {
+                    Body::Circle(i) => Some(i),
+                    _ => None,
+                });
+                if let Some(i) = under_mouse {
+                    let last = self.world.circles.len() - 1;
+                    self.world.despawn(i);
+                    self.despawned(i, if last != i { Some(last) } else { None });
                 }
-                // handle colliding circle. If they are hit reflect their speed and make them move accordingly
-                // The hit ball hits in the direction tangent of the colision while the hitter moves direction of the normal vector
-                for pair in &colliding_circles {
-                    let first = circles[pair.0];
-
-                    let second = if pair.2 {
-                        fake_balls[pair.1]
-                    } else {
-                        circles[pair.1]
-                    };
-
-                    let distance = second.center - first.center;
-                    let normal = distance.unit_vector();
-                    let tangental = normal.perpendicular();
-
-                    let tan_speed1 = FVec2D::dot(first.speed, tangental);
-                    let tan_speed2 = FVec2D::dot(second.speed, tangental);
-
-                    let norm_speed1 = FVec2D::dot(first.speed, normal);
-                    let norm_speed2 = FVec2D::dot(second.speed, normal);
-
-                    // conservation of momentum in 1D
-                    // elastic collisions https://en.wikipedia.org/wiki/Elastic_collision
-                    //https://www.youtube.com/watch?v=LPzyNOHY3A4&t=1077s&ab_channel=javidx9
-                    let m1 = ((norm_speed1 * (first.mass - second.mass))
-                        + 2.0 * second.mass * norm_speed2)
-                        / (first.mass + second.mass);
-                    let m2 = ((norm_speed2 * (second.mass - first.mass))
-                        + 2.0 * first.mass * norm_speed1)
-                        / (first.mass + second.mass);
-
-                    // Update with new speeds and all circles are in original vector
-                    if !pair.2 {
-                        circles[pair.0].speed = tangental * tan_speed1 + normal * m1;
-                        circles[pair.1].speed = tangental * tan_speed2 + normal * m2;
-                    } else {
-                        // second circle is fake ball
-                        circles[pair.0].speed = tangental * tan_speed1 + normal * m1;
-                        // fake_balls[pair.1].speed = tangental * tan_speed2 + normal * m2;
-                    }
+            }
+            // place a fountain of short lived circles with E
+            if input.key_pressed(VirtualKeyCode::E) {
+                let seed = self.world.emitters.len() as u64;
+                self.world.emitters.push(Emitter::new(mouse, 30.0, seed));
+            }
+        }
+        if input.key_pressed(VirtualKeyCode::O) {
+            self.world.boundary = match self.world.boundary {
+                Boundary::Wrap => Boundary::Open,
+                Boundary::Open => Boundary::Wrap,
+            };
+            println!("Boundary: {:?}", self.world.boundary);
+        }
+
+        self.world.step(delta_t.as_secs_f32());
+        for event in self.world.events.clone() {
+            match event {
+                Event::Despawned {
+                    index, replaced_by, ..
+                } => self.despawned(index, replaced_by),
+                Event::JointBroken { force, .. } => println!("Joint broke at {:.0}", force),
+                Event::ClusterShattered { impulse, .. } => {
+                    println!("Cluster shattered by an impulse of {:.0}", impulse)
                 }
-                colliding_circles.clear();
-                fake_balls.clear();
+                Event::RevoluteJointBroken { force, torque, .. } => println!(
+                    "Pin broke at a force of {:.0} and a torque of {:.0}",
+                    force, torque
+                ),
             }
         }
 
+        if input.key_pressed(VirtualKeyCode::T) {
+            self.trails = !self.trails;
+        }
+        if input.key_pressed(VirtualKeyCode::A) {
+            canvas.set_antialiasing(!canvas.antialiasing());
+        }
+        if input.key_pressed(VirtualKeyCode::D) {
+            self.show_diagnostics = !self.show_diagnostics;
+        }
+        if input.key_pressed(VirtualKeyCode::C) {
+            match self.world.history.save_csv("diagnostics.csv") {
+                Ok(()) => println!("Saved diagnostics to diagnostics.csv"),
+                Err(error) => println!("Could not save diagnostics: {}", error),
+            }
+        }
+        if input.key_pressed(VirtualKeyCode::I) {
+            self.world.integrator = match self.world.integrator {
+                Integrator::SemiImplicitEuler => Integrator::VelocityVerlet,
+                Integrator::VelocityVerlet => Integrator::PositionVerlet,
+                Integrator::PositionVerlet => Integrator::SemiImplicitEuler,
+            };
+            println!("Integrator: {:?}", self.world.integrator);
+        }
+        if input.key_pressed(VirtualKeyCode::P) {
+            self.world.solver = match self.world.solver {
+                Solver::Impulse => Solver::Xpbd,
+                Solver::Xpbd => Solver::Impulse,
+            };
+            println!("Solver: {:?}", self.world.solver);
+        }
+        if input.key_pressed(VirtualKeyCode::G) {
+            self.world.granular = match self.world.granular {
+                None => Some(Granular::sand()),
+                Some(granular) if granular == Granular::sand() => Some(Granular::wet_sand()),
+                Some(_) => None,
+            };
+            println!("Granular: {:?}", self.world.granular);
+        }
+
+        self.world.fluid.draw(canvas);
+
         // draw circles
-        for circle in circles.iter() {
+        for circle in self.world.circles.iter() {
             canvas.filled_circle(circle.center.to_i32(), circle.radius as i32, circle.color);
         }
 
+        // translucent glow over the selected circle
+        if let Some(index) = self.selected_circle {
+            let circle = self.world.circles.get(index);
+            canvas.with_blend_mode(BlendMode::Screen, |canvas| {
+                canvas.filled_circle(
+                    circle.center.to_i32(),
+                    circle.radius as i32 + 3,
+                    Color::rgba(255, 255, 255, 90),
+                )
+            });
+        }
+
+        for car in self.cars.iter() {
+            car.draw(canvas, &self.world.circles);
+        }
+
         // draw line segments
-        for line in self.lines.iter() {
-            canvas.filled_circle(line.start.to_i32(), line.radius as i32, color::WHITE);
-            canvas.filled_circle(line.end.to_i32(), line.radius as i32, color::WHITE);
+        for line in self.world.lines.iter() {
+            line.draw(canvas, color::WHITE);
+        }
 
-            // one line on bottom of the circles
-            let normal = (line.end - line.start).perpendicular().unit_vector();
-            let line_start = (normal * line.radius) + line.start;
-            let line_end = (normal * line.radius) + line.end;
-            canvas.line_between(line_start.to_i32(), line_end.to_i32(), color::WHITE);
+        if self.show_diagnostics {
+            self.world
+                .diagnostics
+                .draw_overlay(canvas, Point2D::new(10, 10), 1, color::WHITE);
+            let graph_width = 300;
+            let graph_height = 100;
+            self.world.history.draw_graph(
+                canvas,
+                Point2D::new(self.width as i32 - graph_width - 10, 10),
+                graph_width,
+                graph_height,
+            );
+        }
 
-            // another line on top of circle
-            let line_start = -(normal * line.radius) + line.start;
-            let line_end = -(normal * line.radius) + line.end;
-            canvas.line_between(line_start.to_i32(), line_end.to_i32(), color::WHITE);
+        // once the frame is drawn
+        if input.key_pressed(VirtualKeyCode::S) {
+            match canvas.save_png("screenshot.png", PngCompression::Deflate) {
+                Ok(()) => println!("Saved the frame to screenshot.png"),
+                Err(error) => println!("Could not save screenshot: {}", error),
+            }
         }
 
         return true;
@@ -323,16 +476,15 @@ impl Render2D for Drawable {
     }
 }
 
-fn circles_overlap(c1: &Circle, c2: &Circle) -> bool {
-    let vec = (c2.center - c1.center).to_f32();
-    vec.squared_length() <= (c1.radius + c2.radius) * (c1.radius + c2.radius)
-}
-
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub struct Circle {
     pub center: FVec2D,
     pub radius: f32,
     pub speed: FVec2D,
+    /// Rotation in radians, clockwise on screen
+    pub angle: f32,
+    /// Rotation speed in radians per second
+    pub angular_speed: f32,
     pub sim_time_remaining: f32,
     pub prev_center: FVec2D,
     pub mass: f32,
@@ -342,16 +494,13 @@ pub struct Circle {
 }
 
 impl Circle {
-    pub fn hits(&self, current: Point2D, width: f32) -> bool {
-        let delta_vec = current.to_f32() - self.center;
-        let distance_from_center = self.radius * self.radius - delta_vec.squared_length();
-        distance_from_center < width * width && distance_from_center > 0.0
-    }
     pub fn new(center: FVec2D, radius: f32, speed: FVec2D, color: Color) -> Self {
         Self {
             center,
             radius,
             speed,
+            angle: 0.0,
+            angular_speed: 0.0,
             mass: radius * 10.0,
             acceletation: FVec2D::new(0.0, 0.0),
             color,
@@ -361,6 +510,45 @@ impl Circle {
         }
     }
 
+    /// Check whether a point lies inside the circle or on its edge
+    pub fn contains_point(&self, point: FVec2D) -> bool {
+        Self::disc_contains_point(self.center, self.radius, point)
+    }
+
+    /// Same as contains_point for a circle given by its center and radius,
+    /// for bodies that are not stored as a Circle
+    pub fn disc_contains_point(center: FVec2D, radius: f32, point: FVec2D) -> bool {
+        (point - center).squared_length() <= radius * radius
+    }
+
+    /// Distance from the point to the edge of the circle.
+    /// Negative when the point is inside the circle, zero on the edge and positive outside
+    pub fn signed_distance(&self, point: FVec2D) -> f32 {
+        (point - self.center).length() - self.radius
+    }
+
+    /// Closest point on the edge of the circle to the given point.
+    /// The center is equally far from every point on the edge, so the rightmost one is picked
+    pub fn closest_point(&self, point: FVec2D) -> FVec2D {
+        let delta = point - self.center;
+        if delta.squared_length() == 0.0 {
+            return self.center + FVec2D::new(self.radius, 0.0);
+        }
+        self.center + delta.unit_vector() * self.radius
+    }
+
+    /// Check whether two circles overlap (touching counts as overlapping)
+    pub fn intersects_circle(&self, other: &Circle) -> bool {
+        let reach = self.radius + other.radius;
+        (other.center - self.center).squared_length() <= reach * reach
+    }
+
+    /// Check whether the circle overlaps an edge (a capsule around a line segment)
+    pub fn intersects_capsule(&self, capsule: &LineSegment) -> bool {
+        let reach = self.radius + capsule.radius;
+        (capsule.closest_point(self.center) - self.center).squared_length() <= reach * reach
+    }
+
     pub fn draw(&self, canvas: &mut Canvas) {
         canvas.filled_circle(self.center.to_i32(), self.radius as i32, self.color);
     }
@@ -370,7 +558,9 @@ enum LineSelection {
     Head(usize),
     Tail(usize),
 }
-struct LineSegment {
+/// Static edge shaped like a capsule: a segment from start to end with rounded ends of the given radius
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct LineSegment {
     pub start: FVec2D,
     pub end: FVec2D,
     pub radius: f32,
@@ -380,4 +570,26 @@ impl LineSegment {
     pub fn new(start: FVec2D, end: FVec2D, radius: f32) -> Self {
         Self { start, end, radius }
     }
+
+    /// Draw the edge as a solid capsule
+    pub fn draw(&self, canvas: &mut Canvas, color: Color) {
+        canvas.thick_line(
+            self.start,
+            self.end,
+            self.radius * 2.0,
+            LineCap::Round,
+            color,
+        );
+    }
+
+    /// Closest point to the given point on the segment running from start to end
+    pub fn closest_point(&self, point: FVec2D) -> FVec2D {
+        let line_segment = self.end - self.start;
+        let segment_length = line_segment.squared_length();
+        if segment_length == 0.0 {
+            return self.start;
+        }
+        let t = FVec2D::dot(point - self.start, line_segment) / segment_length;
+        self.start + line_segment * t.clamp(0.0, 1.0)
+    }
 }
@@ -1,12 +1,64 @@
+use crate::grfx::clip;
+use crate::grfx::clip::ClipRect;
 use crate::grfx::color;
-use crate::grfx::color::Color;
-use crate::math::vector::{FVec2D, Point2D};
+use crate::grfx::color::{BlendMode, Color};
+use crate::grfx::font;
+use crate::grfx::netpbm;
+use crate::grfx::netpbm::PpmFormat;
+use crate::grfx::png;
+use crate::grfx::png::PngCompression;
+use crate::grfx::sprite::{Sprite, SpriteRect};
+use crate::math::vector::{FVec2D, Point2D, Vector2D};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Shape drawn at the open ends of thick lines
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum LineCap {
+    /// The line stops square at its end points
+    #[default]
+    Butt,
+    /// Half a circle around each end point
+    Round,
+    /// The line runs on past its end points by half its width
+    Square,
+}
+
+/// Shape drawn where two lines of a thick polyline meet
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum LineJoin {
+    /// The outer edges run on until they meet, sharp corners past the miter limit are beveled
+    #[default]
+    Miter,
+    /// A circle around the corner
+    Round,
+    /// The outer corners of the two lines are joined straight across
+    Bevel,
+}
+
+/// Which points a polygon whose outline crosses itself covers
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum FillRule {
+    /// Points the outline winds around any number of times, crossings and overlaps are filled
+    #[default]
+    NonZero,
+    /// Points the outline winds around an odd number of times, overlaps leave holes
+    EvenOdd,
+}
+
+/// Longest a miter may be, as a multiple of the line width, before the corner is beveled instead
+const MITER_LIMIT: f32 = 4.0;
 
 #[allow(dead_code, unused_variables)]
 pub struct Canvas {
     width: u32,
     height: u32,
     pub pixels: Vec<Color>,
+    blend_mode: BlendMode,
+    antialiasing: bool,
+    // always inside the canvas
+    clip: ClipRect,
 }
 impl Canvas {
     pub fn new(width: u32, height: u32) -> Self {
@@ -19,9 +71,149 @@ impl Canvas {
             width,
             height,
             pixels,
+            blend_mode: BlendMode::default(),
+            antialiasing: false,
+            clip: ClipRect::new(0, 0, width, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    ///
+    /// Save the canvas as a PPM image, alpha is dropped
+    ///     path -> file to write
+    ///     format -> binary (P6) or plain text (P3)
+    ///
+    pub fn save_ppm<P: AsRef<Path>>(&self, path: P, format: PpmFormat) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(&netpbm::encode_ppm(
+            self.width,
+            self.height,
+            &self.pixels,
+            format,
+        ))
+    }
+
+    /// Canvas holding a P3 or P6 image, the size of the image
+    pub fn load_ppm<P: AsRef<Path>>(path: P) -> std::io::Result<Canvas> {
+        let (width, height, pixels) = netpbm::decode_ppm(&std::fs::read(path)?)?;
+        Ok(Canvas::from_pixels(width, height, pixels))
+    }
+
+    /// Save the canvas as a PAM image, alpha included
+    pub fn save_pam<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(&netpbm::encode_pam(self.width, self.height, &self.pixels))
+    }
+
+    /// Canvas holding a PAM image, the size of the image
+    pub fn load_pam<P: AsRef<Path>>(path: P) -> std::io::Result<Canvas> {
+        let (width, height, pixels) = netpbm::decode_pam(&std::fs::read(path)?)?;
+        Ok(Canvas::from_pixels(width, height, pixels))
+    }
+
+    ///
+    /// Save the canvas as an 8 bit RGBA PNG image
+    ///     path -> file to write
+    ///     compression -> deflate compressed or stored
+    ///
+    pub fn save_png<P: AsRef<Path>>(
+        &self,
+        path: P,
+        compression: PngCompression,
+    ) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(&png::encode_png(
+            self.width,
+            self.height,
+            &self.pixels,
+            compression,
+        ))
+    }
+
+    /// Canvas holding an 8 bit RGB or RGBA PNG image, the size of the image
+    pub fn load_png<P: AsRef<Path>>(path: P) -> std::io::Result<Canvas> {
+        let (width, height, pixels) = png::decode_png(&std::fs::read(path)?)?;
+        Ok(Canvas::from_pixels(width, height, pixels))
+    }
+
+    // Canvas around pixels already decoded, they must number width * height
+    fn from_pixels(width: u32, height: u32, pixels: Vec<Color>) -> Canvas {
+        Self {
+            width,
+            height,
+            pixels,
+            clip: ClipRect::new(0, 0, width, height),
+            ..Canvas::new(0, 0)
         }
     }
 
+    /// How everything drawn from now on combines with the pixels already on the canvas
+    pub fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.blend_mode = mode;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    ///
+    /// Draw with the given blend mode, the blend mode of the canvas is restored afterwards
+    ///     mode -> blend mode used by everything drawn inside draw
+    ///     draw -> drawing calls
+    ///
+    pub fn with_blend_mode<F: FnOnce(&mut Canvas)>(&mut self, mode: BlendMode, draw: F) {
+        let previous = self.blend_mode;
+        self.blend_mode = mode;
+        draw(self);
+        self.blend_mode = previous;
+    }
+
+    ///
+    /// Draw lines and circles with smooth anti-aliased edges instead of crisp pixel steps.
+    /// The edge pixels are blended into the canvas with the blend mode of the canvas.
+    ///
+    pub fn set_antialiasing(&mut self, antialiasing: bool) {
+        self.antialiasing = antialiasing;
+    }
+
+    pub fn antialiasing(&self) -> bool {
+        self.antialiasing
+    }
+
+    ///
+    /// Confine everything drawn from now on to a rectangle, pixels outside of it are left alone.
+    /// None draws on the whole canvas again. Clearing the canvas ignores the clip.
+    ///
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        let canvas = ClipRect::new(0, 0, self.width, self.height);
+        self.clip = clip.map_or(canvas, |clip| clip.intersection(canvas));
+    }
+
+    /// Rectangle drawing is confined to, the part of the clip rectangle on the canvas
+    pub fn clip(&self) -> ClipRect {
+        self.clip
+    }
+
+    ///
+    /// Draw confined to a rectangle inside the current clip, the clip is restored afterwards.
+    /// Panels nest: drawing inside draw stays within every rectangle it is wrapped in.
+    ///     clip -> rectangle everything drawn inside draw is confined to
+    ///     draw -> drawing calls
+    ///
+    pub fn with_clip<F: FnOnce(&mut Canvas)>(&mut self, clip: ClipRect, draw: F) {
+        let previous = self.clip;
+        self.clip = previous.intersection(clip);
+        draw(self);
+        self.clip = previous;
+    }
+
     pub fn clear(&mut self, color: Color) {
         for pixel in &mut self.pixels {
             *pixel = color;
@@ -29,20 +221,67 @@ impl Canvas {
     }
 
     ///
-    ///  Plots a single pixel at the given coordinates
+    ///  Plots a single pixel at the given coordinates, blended with the blend mode of the canvas
     ///     x  -> X axis offset
     ///     y  -> y axis offset
     ///     color -> pixel color
     ///
     pub fn plot(&mut self, x: i32, y: i32, color: Color) {
-        if x >= 0 && x < self.width as i32 && y >= 0 && y < self.height as i32 {
+        self.plot_blended(x, y, color, self.blend_mode);
+    }
+
+    ///
+    ///  Plots a single pixel at the given coordinates with the given blend mode
+    ///     x  -> X axis offset
+    ///     y  -> y axis offset
+    ///     color -> pixel color
+    ///     mode -> how the color combines with the pixel
+    ///
+    pub fn plot_blended(&mut self, x: i32, y: i32, color: Color, mode: BlendMode) {
+        if self.clip.contains(x, y) {
             let normalized_position = (y * self.width as i32 + x) as usize;
             if normalized_position < self.pixels.len() {
-                self.pixels[normalized_position] = color;
+                let pixel = &mut self.pixels[normalized_position];
+                *pixel = color.blend(*pixel, mode);
             }
         }
     }
 
+    ///
+    ///  Plots a pixel partly covered by a shape, the color is made as see through as the pixel is uncovered
+    ///     x  -> X axis offset
+    ///     y  -> y axis offset
+    ///     color -> pixel color
+    ///     coverage -> fraction of the pixel covered, from 0 to 1
+    ///
+    pub fn plot_coverage(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        let coverage = coverage.clamp(0.0, 1.0);
+        if coverage > 0.0 {
+            let alpha = (color.alpha() as f32 * coverage).round() as u8;
+            self.plot(x, y, Color::rgba(color.r(), color.g(), color.b(), alpha));
+        }
+    }
+
+    // Plot every pixel from x_start to x_end on row y once, so translucent colors blend evenly
+    fn span(&mut self, y: i32, x_start: i32, x_end: i32, color: Color) {
+        let (left, top, right, bottom) = self.clip.edges();
+        if y < top || y > bottom {
+            return;
+        }
+        for x in x_start.max(left)..=x_end.min(right) {
+            self.plot(x, y, color);
+        }
+    }
+
+    // Whether everything within radius pixels of origin lies outside the clip rectangle
+    fn misses(&self, origin: Point2D, radius: i32) -> bool {
+        let (left, top, right, bottom) = self.clip.edges();
+        origin.x + radius < left
+            || origin.x - radius > right
+            || origin.y + radius < top
+            || origin.y - radius > bottom
+    }
+
     pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: Color) {
         let origin = Point2D::new(x0, y0);
         let end = Point2D::new(x1, y1);
@@ -55,8 +294,13 @@ impl Canvas {
     /// dest: final point
     /// color: Pixel color
     ///
+    /// Lines are clipped with Liang-Barsky first, only the steps inside the clip rectangle are walked.
     /// https://www.programmersought.com/article/60715259426/
     pub fn line_between(&mut self, origin: Point2D, dest: Point2D, color: Color) {
+        if self.antialiasing {
+            self.smooth_line(origin.to_f32(), dest.to_f32(), color);
+            return;
+        }
         let x0 = origin.x;
         let y0 = origin.y;
 
@@ -67,50 +311,64 @@ impl Canvas {
         let dy = if x1 >= x0 { y1 - y0 } else { y0 - y1 };
 
         // ensure we go from smaller to highest
-        let mut x = if x1 >= x0 { x0 } else { x1 };
-        let mut y = if x1 >= x0 { y0 } else { y1 };
+        let x = if x1 >= x0 { x0 } else { x1 };
+        let y = if x1 >= x0 { y0 } else { y1 };
 
         // veritical line
         if dx == 0 {
-            for i in y0.min(y1)..y0.max(y1) {
+            let (left, top, right, bottom) = self.clip.edges();
+            if x < left || x > right {
+                return;
+            }
+            for i in y0.min(y1).max(top)..=y0.max(y1).min(bottom) {
                 self.plot(x, i, color);
             }
             return;
         }
         // horizontal line
         if dy == 0 {
-            for i in x0.min(x1)..x0.max(x1) {
-                self.plot(i, y, color);
-            }
+            self.span(y, x0.min(x1), x0.max(x1), color);
             return;
         }
-        // slope is less than 1
-        if dy.abs() <= dx {
-            let mut decision = 2 * dy.abs() - dx;
-            self.plot(x, y, color);
-            while x < x0.max(x1) {
-                x += 1;
-                if decision < 0 {
-                    decision = decision + 2 * dy.abs();
-                } else {
-                    y += if dy >= 0 { 1 } else { -1 };
-                    decision = decision + 2 * (dy.abs() - dx);
-                }
-                self.plot(x, y, color);
-            }
+
+        // steps along x when the slope is less than 1, along y otherwise with the axes swapped
+        let steep = dy.abs() > dx;
+        let (major, minor) = if steep {
+            (dy.abs() as i64, dx as i64)
         } else {
-            // slopw is > 1 and dy positions are swapped
-            let mut decision = 2 * dx - dy.abs();
-            self.plot(x, y, color);
-            while x < x0.max(x1) {
-                y += if dy >= 0 { 1 } else { -1 };
-                if decision < 0 {
-                    decision = decision + 2 * dx;
-                } else {
-                    x += 1;
-                    decision = decision + 2 * (dx - dy.abs());
-                }
-                self.plot(x, y, color);
+            (dx as i64, dy.abs() as i64)
+        };
+        let direction = if dy >= 0 { 1 } else { -1 };
+
+        // every pixel is within half a pixel of the line, so the line clipped to the clip rectangle
+        // grown by a pixel covers all the steps that can land on it
+        let (enter, leave) = match clip::clip_line(
+            Vector2D::new(x as f64, y as f64),
+            Vector2D::new((x + dx) as f64, (y + dy) as f64),
+            self.clip.bounds(1.0),
+        ) {
+            Some(range) => range,
+            None => return,
+        };
+        let first = ((enter * major as f64).floor() as i64).max(0);
+        let last = ((leave * major as f64).ceil() as i64).min(major);
+
+        // jump straight to the first step: after k steps the line has moved
+        // (2 * minor * k + major) / (2 * major) pixels along the other axis
+        let mut moved = (2 * minor * first + major) / (2 * major);
+        let mut decision = 2 * minor * (first + 1) - major - 2 * major * moved;
+        for step in first..=last {
+            let (along, across) = (step as i32, moved as i32);
+            if steep {
+                self.plot(x + across, y + along * direction, color);
+            } else {
+                self.plot(x + along, y + across * direction, color);
+            }
+            if decision < 0 {
+                decision += 2 * minor;
+            } else {
+                moved += 1;
+                decision += 2 * (minor - major);
             }
         }
     }
@@ -123,6 +381,13 @@ impl Canvas {
     /// https://iq.opengenus.org/bresenhams-circle-drawing-algorithm/
     ///
     pub fn circle(&mut self, origin: Point2D, radius: i32, color: Color) {
+        if self.antialiasing {
+            self.smooth_circle(origin.to_f32(), radius as f32, color);
+            return;
+        }
+        if self.misses(origin, radius) {
+            return;
+        }
         let mut x = 0;
         let mut y = radius;
         let mut decision = 3 - 2 * y;
@@ -149,7 +414,7 @@ impl Canvas {
     }
 
     ///
-    /// Draws a hollow rectangle
+    /// Draws a hollow rectangle, the corners are origin and origin + (width, height)
     /// Takes:
     /// origin: toip left corner
     /// Width
@@ -157,18 +422,31 @@ impl Canvas {
     /// Color for pixels
     ///
     pub fn rectangle(&mut self, origin: Point2D, width: i32, height: i32, color: Color) {
-        let top_right = Point2D::new(origin.x + width, origin.y);
-        let bottom_left = Point2D::new(origin.x, origin.y + height);
-        let bottom_right = Point2D::new(origin.x + width, origin.y + height);
+        let (left, right) = (
+            origin.x.min(origin.x + width),
+            origin.x.max(origin.x + width),
+        );
+        let (top, bottom) = (
+            origin.y.min(origin.y + height),
+            origin.y.max(origin.y + height),
+        );
 
-        self.line_between(origin, top_right, color);
-        self.line_between(origin, bottom_left, color);
-        self.line_between(bottom_left, bottom_right, color);
-        self.line_between(top_right, bottom_right, color);
+        // each corner is drawn once, so translucent outlines have no bright corners
+        self.span(top, left, right, color);
+        if bottom > top {
+            self.span(bottom, left, right, color);
+        }
+        let (_, clip_top, _, clip_bottom) = self.clip.edges();
+        for y in (top + 1).max(clip_top)..bottom.min(clip_bottom + 1) {
+            self.plot(left, y, color);
+            if right > left {
+                self.plot(right, y, color);
+            }
+        }
     }
 
     ///
-    /// Draws a hollow rectangle
+    /// Draws a filled rectangle, the corners are origin and origin + (width, height)
     /// Takes:
     /// origin: toip left corner
     /// Width
@@ -176,15 +454,22 @@ impl Canvas {
     /// Color for pixels
     ///
     pub fn filled_rectangle(&mut self, origin: Point2D, width: i32, height: i32, color: Color) {
-        for i in 0..=height {
-            let right = Point2D::new(origin.x, origin.y + i);
-            let left = Point2D::new(origin.x + width, origin.y + i);
-            self.line_between(right, left, color);
+        let (left, right) = (
+            origin.x.min(origin.x + width),
+            origin.x.max(origin.x + width),
+        );
+        let (top, bottom) = (
+            origin.y.min(origin.y + height),
+            origin.y.max(origin.y + height),
+        );
+        let (_, clip_top, _, clip_bottom) = self.clip.edges();
+        for y in top.max(clip_top)..=bottom.min(clip_bottom) {
+            self.span(y, left, right, color);
         }
     }
 
     ///
-    /// Draws a hollow rectangle
+    /// Draws a hollow triangle
     /// Takes:
     /// v1 first point
     /// v2 second point
@@ -197,6 +482,137 @@ impl Canvas {
         self.line_between(v3, v1, color);
     }
 
+    ///
+    /// Draws a filled triangle
+    /// Takes:
+    /// v1 first point
+    /// v2 second point
+    /// v3 third point
+    /// color: Color for the pixels
+    ///
+    /// A pixel is filled when its center is inside the triangle. Centers right on an edge follow
+    /// the top-left rule: they are filled for top and left edges only, so triangles sharing
+    /// an edge never both draw the same pixel, and translucent meshes have no seams.
+    ///
+    pub fn filled_triangle(&mut self, v1: Point2D, v2: Point2D, v3: Point2D, color: Color) {
+        // edge function, positive on the inner side of the edge from a to b
+        let edge = |a: Point2D, b: Point2D, x: i32, y: i32| -> i64 {
+            (b.x as i64 - a.x as i64) * (y as i64 - a.y as i64)
+                - (b.y as i64 - a.y as i64) * (x as i64 - a.x as i64)
+        };
+        let (v2, v3) = match edge(v1, v2, v3.x, v3.y) {
+            0 => return,
+            area if area < 0 => (v3, v2),
+            _ => (v2, v3),
+        };
+        // pixels right on a top or left edge are in, right on any other edge they are out
+        let bias = |a: Point2D, b: Point2D| {
+            let top = b.y == a.y && b.x > a.x;
+            let left = b.y < a.y;
+            if top || left {
+                0
+            } else {
+                -1
+            }
+        };
+        let edges = [
+            (v1, v2, bias(v1, v2)),
+            (v2, v3, bias(v2, v3)),
+            (v3, v1, bias(v3, v1)),
+        ];
+
+        let (clip_left, clip_top, clip_right, clip_bottom) = self.clip.edges();
+        let left = v1.x.min(v2.x).min(v3.x).max(clip_left);
+        let right = v1.x.max(v2.x).max(v3.x).min(clip_right);
+        let top = v1.y.min(v2.y).min(v3.y).max(clip_top);
+        let bottom = v1.y.max(v2.y).max(v3.y).min(clip_bottom);
+        for y in top..=bottom {
+            for x in left..=right {
+                let inside = edges
+                    .iter()
+                    .all(|&(a, b, bias)| edge(a, b, x, y) + bias >= 0);
+                if inside {
+                    self.plot(x, y, color);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Draws a filled polygon, the outline may cross itself
+    /// vertices -> Points of the outline, the last one joins back to the first
+    /// rule -> which parts of a self crossing outline are filled
+    /// color -> color to paint them
+    ///
+    /// The outline is clipped with Sutherland-Hodgman first, then filled row by row from
+    /// its crossings with each row of pixel centers.
+    /// Centers on the outline follow the same top-left rule as filled_triangle.
+    ///
+    pub fn filled_polygon(&mut self, vertices: &[Point2D], rule: FillRule, color: Color) {
+        if vertices.len() < 3 {
+            return;
+        }
+        // clipped a pixel outside the clip rectangle, the new edges along it are never drawn
+        let vertices: Vec<Vector2D<f64>> = vertices
+            .iter()
+            .map(|v| Vector2D::new(v.x as f64, v.y as f64))
+            .collect();
+        let vertices = clip::clip_polygon(&vertices, self.clip.bounds(1.0));
+        if vertices.len() < 3 {
+            return;
+        }
+        let (left, clip_top, right, clip_bottom) = self.clip.edges();
+        let top = vertices
+            .iter()
+            .map(|v| v.y.ceil() as i32)
+            .min()
+            .unwrap_or(0)
+            .max(clip_top);
+        let bottom = vertices
+            .iter()
+            .map(|v| v.y.floor() as i32)
+            .max()
+            .unwrap_or(0)
+            .min(clip_bottom);
+        // first pixel right of every crossing, with +1 for edges going down and -1 for edges going up
+        let mut crossings: Vec<(i32, i32)> = Vec::new();
+        for y in top..=bottom {
+            crossings.clear();
+            let row = y as f64;
+            for (i, &start) in vertices.iter().enumerate() {
+                let end = vertices[(i + 1) % vertices.len()];
+                let (upper, lower, winding) = if start.y < end.y {
+                    (start, end, 1)
+                } else {
+                    (end, start, -1)
+                };
+                // rows on the top end of an edge count, rows on the bottom end do not
+                if start.y == end.y || row < upper.y || row >= lower.y {
+                    continue;
+                }
+                let x = upper.x + (row - upper.y) * (lower.x - upper.x) / (lower.y - upper.y);
+                // crossings of unclipped edges are whole or at least 1 / rise away from whole,
+                // far more than any rounding error
+                crossings.push(((x - 1e-7).ceil() as i32, winding));
+            }
+            crossings.sort_unstable();
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside && pair[0].0 < pair[1].0 {
+                    let start = pair[0].0.max(left);
+                    let end = pair[1].0.min(right + 1);
+                    self.span(y, start, end - 1, color);
+                }
+            }
+        }
+    }
+
     ///
     /// Draw a given polygone based on the given vertices/points vector
     /// vertices -> Points to connect
@@ -275,38 +691,24 @@ impl Canvas {
     /// https://github.com/OneLoneCoder/olcPixelGameEngine/blob/master/olcPixelGameEngine.h  (Javidx9  github)
     ///
     pub fn filled_circle(&mut self, origin: Point2D, radius: i32, color: Color) {
+        if radius < 0 {
+            return;
+        }
+        if self.antialiasing {
+            self.smooth_filled_circle(origin.to_f32(), radius as f32, color);
+            return;
+        }
+        if self.misses(origin, radius) {
+            return;
+        }
+        // half width of every row, taken from the outline, so each pixel is only drawn once
+        let mut half_widths = vec![0; radius as usize + 1];
         let mut x = 0;
         let mut y = radius;
         let mut decision = 3 - 2 * y;
         while y >= x {
-            self.line(
-                origin.x + x,
-                origin.y - y,
-                origin.x + x,
-                origin.y + y,
-                color,
-            );
-            self.line(
-                origin.x - x,
-                origin.y - y,
-                origin.x - x,
-                origin.y + y,
-                color,
-            );
-            self.line(
-                origin.x - y,
-                origin.y - x,
-                origin.x + y,
-                origin.y - x,
-                color,
-            );
-            self.line(
-                origin.x - y,
-                origin.y + x,
-                origin.x + y,
-                origin.y + x,
-                color,
-            );
+            half_widths[y as usize] = half_widths[y as usize].max(x);
+            half_widths[x as usize] = half_widths[x as usize].max(y);
 
             x += 1;
             if decision > 0 {
@@ -316,5 +718,530 @@ impl Canvas {
                 decision = decision + 4 * x + 6;
             }
         }
+        for (row, &half_width) in half_widths.iter().enumerate() {
+            let row = row as i32;
+            self.span(
+                origin.y + row,
+                origin.x - half_width,
+                origin.x + half_width,
+                color,
+            );
+            if row > 0 {
+                self.span(
+                    origin.y - row,
+                    origin.x - half_width,
+                    origin.x + half_width,
+                    color,
+                );
+            }
+        }
+    }
+
+    ///
+    /// Draws an anti-aliased line with Xiaolin Wu's algorithm, the ends may sit between pixels
+    /// start: start point
+    /// end: final point
+    /// color: Pixel color
+    ///
+    /// Each step along the line is shared between the two pixels closest to it by how near
+    /// the line passes their centers. The pixels at both ends are drawn whole.
+    /// Only the steps inside the clip rectangle are walked, found with Liang-Barsky.
+    /// https://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm
+    ///
+    pub fn smooth_line(&mut self, start: FVec2D, end: FVec2D, color: Color) {
+        let steep = (end.y - start.y).abs() > (end.x - start.x).abs();
+        // walk along x, swapping the axes for steep lines
+        let (mut start, mut end) = if steep {
+            (FVec2D::new(start.y, start.x), FVec2D::new(end.y, end.x))
+        } else {
+            (start, end)
+        };
+        if start.x > end.x {
+            std::mem::swap(&mut start, &mut end);
+        }
+        let run = end.x - start.x;
+        let gradient = if run == 0.0 {
+            0.0
+        } else {
+            (end.y - start.y) / run
+        };
+
+        // both pixels of a step are within a pixel of the line
+        let bounds = if steep {
+            self.clip.bounds(1.0).transposed()
+        } else {
+            self.clip.bounds(1.0)
+        };
+        let (enter, leave) = match clip::clip_line(
+            Vector2D::new(start.x as f64, start.y as f64),
+            Vector2D::new(end.x as f64, end.y as f64),
+            bounds,
+        ) {
+            Some(range) => range,
+            None => return,
+        };
+        let first =
+            (start.x.round() as i32).max((start.x as f64 + enter * run as f64).floor() as i32);
+        let last = (end.x.round() as i32).min((start.x as f64 + leave * run as f64).ceil() as i32);
+
+        for x in first..=last {
+            let y = start.y + gradient * (x as f32 - start.x);
+            let share = y - y.floor();
+            let below = y.floor() as i32;
+            if steep {
+                self.plot_coverage(below, x, color, 1.0 - share);
+                self.plot_coverage(below + 1, x, color, share);
+            } else {
+                self.plot_coverage(x, below, color, 1.0 - share);
+                self.plot_coverage(x, below + 1, color, share);
+            }
+        }
+    }
+
+    ///
+    /// Draws an anti-aliased hollow circle one pixel wide, the center may sit between pixels
+    /// center: Center of circle
+    /// radius: Radius
+    /// color: Pixel color
+    ///
+    pub fn smooth_circle(&mut self, center: FVec2D, radius: f32, color: Color) {
+        self.circle_band(center, radius - 1.0, radius + 1.0, color, |distance| {
+            1.0 - (distance - radius).abs()
+        });
+    }
+
+    ///
+    /// Draws an anti-aliased filled circle, edge pixels are covered by how much of them lies inside
+    /// center: Center of circle
+    /// radius: Radius
+    /// color: Pixel color
+    ///
+    pub fn smooth_filled_circle(&mut self, center: FVec2D, radius: f32, color: Color) {
+        self.circle_band(center, 0.0, radius + 0.5, color, |distance| {
+            radius + 0.5 - distance
+        });
+    }
+
+    // Plot every pixel whose center is between inner and outer away from center once,
+    // with the coverage worked out from that distance
+    fn circle_band<F: Fn(f32) -> f32>(
+        &mut self,
+        center: FVec2D,
+        inner: f32,
+        outer: f32,
+        color: Color,
+        coverage: F,
+    ) {
+        if outer <= 0.0 {
+            return;
+        }
+        let (clip_left, clip_top, clip_right, clip_bottom) = self.clip.edges();
+        let top = ((center.y - outer).floor() as i32).max(clip_top);
+        let bottom = ((center.y + outer).ceil() as i32).min(clip_bottom);
+        for y in top..=bottom {
+            let dy = y as f32 - center.y;
+            if dy.abs() > outer {
+                continue;
+            }
+            let reach = (outer * outer - dy * dy).sqrt();
+            let left = ((center.x - reach).floor() as i32).max(clip_left);
+            let right = ((center.x + reach).ceil() as i32).min(clip_right);
+            // skip the pixels well inside the inner edge, they are not part of the band
+            let mut hole = (right, right + 1);
+            if inner > dy.abs() {
+                let reach = (inner * inner - dy * dy).sqrt();
+                let (start, end) = (
+                    (center.x - reach).ceil() as i32,
+                    (center.x + reach).floor() as i32,
+                );
+                if start < end {
+                    hole = (start, end);
+                }
+            }
+            for x in (left..=hole.0.min(right)).chain(hole.1.max(left)..=right) {
+                let distance = (FVec2D::new(x as f32, y as f32) - center).length();
+                self.plot_coverage(x, y, color, coverage(distance));
+            }
+        }
+    }
+
+    ///
+    /// Draws a line of the given width
+    /// start: start point
+    /// end: final point
+    /// width: Width of the line in pixels
+    /// cap: Shape of the two ends
+    /// color: Pixel color
+    ///
+    pub fn thick_line(
+        &mut self,
+        start: FVec2D,
+        end: FVec2D,
+        width: f32,
+        cap: LineCap,
+        color: Color,
+    ) {
+        self.thick_polyline(&[start, end], width, cap, LineJoin::default(), color);
+    }
+
+    ///
+    /// Draws connected lines of the given width, every pixel is drawn once
+    /// so translucent polylines do not get darker where the lines overlap
+    /// points: Points to connect, in order
+    /// width: Width of the lines in pixels
+    /// cap: Shape of the two open ends
+    /// join: Shape of the corners where lines meet
+    /// color: Pixel color
+    ///
+    pub fn thick_polyline(
+        &mut self,
+        points: &[FVec2D],
+        width: f32,
+        cap: LineCap,
+        join: LineJoin,
+        color: Color,
+    ) {
+        let half_width = width / 2.0;
+        if half_width <= 0.0 {
+            return;
+        }
+        let mut points = points.to_vec();
+        points.dedup();
+        let mut pieces = Vec::new();
+        if points.len() == 1 {
+            // nothing to point the ends along, a round or square dot is all that is left
+            let point = points[0];
+            let corner = FVec2D::new(half_width, half_width);
+            match cap {
+                LineCap::Butt => (),
+                LineCap::Round => pieces.push(Piece::new(vec![point], half_width)),
+                LineCap::Square => pieces.push(Piece::new(
+                    vec![
+                        point - corner,
+                        point + FVec2D::new(corner.x, -corner.y),
+                        point + corner,
+                        point + FVec2D::new(-corner.x, corner.y),
+                    ],
+                    0.0,
+                )),
+            }
+        }
+
+        let last = points.len().saturating_sub(2);
+        for (i, pair) in points.windows(2).enumerate() {
+            let (start, end) = (pair[0], pair[1]);
+            let direction = (end - start).unit_vector();
+            let normal = direction.perpendicular() * half_width;
+            // only the open ends of the polyline get a cap
+            let reach = |open: bool| match cap {
+                LineCap::Square if open => direction * half_width,
+                _ => FVec2D::new(0.0, 0.0),
+            };
+            let (start, end) = (start - reach(i == 0), end + reach(i == last));
+            pieces.push(Piece::new(
+                vec![start + normal, end + normal, end - normal, start - normal],
+                0.0,
+            ));
+            if cap == LineCap::Round {
+                if i == 0 {
+                    pieces.push(Piece::new(vec![pair[0]], half_width));
+                }
+                if i == last {
+                    pieces.push(Piece::new(vec![pair[1]], half_width));
+                }
+            }
+        }
+
+        for corner in points.windows(3) {
+            let (before, point, after) = (corner[0], corner[1], corner[2]);
+            if join == LineJoin::Round {
+                pieces.push(Piece::new(vec![point], half_width));
+                continue;
+            }
+            let incoming = (point - before).unit_vector();
+            let outgoing = (after - point).unit_vector();
+            if FVec2D::cross(incoming, outgoing).abs() < 1e-6 {
+                continue;
+            }
+            // the gap to fill is on the outer side of the corner, the side the outgoing line leaves
+            let (first_normal, second_normal) =
+                (incoming.perpendicular(), outgoing.perpendicular());
+            let side = if FVec2D::dot(second_normal, incoming) < 0.0 {
+                -half_width
+            } else {
+                half_width
+            };
+            let first_corner = point + first_normal * side;
+            let second_corner = point + second_normal * side;
+            let bisector = (first_normal + second_normal).unit_vector();
+            let miter = 1.0 / FVec2D::dot(bisector, first_normal);
+            if join == LineJoin::Miter && miter <= MITER_LIMIT {
+                let tip = point + bisector * (side * miter);
+                pieces.push(Piece::new(
+                    vec![point, first_corner, tip, second_corner],
+                    0.0,
+                ));
+            } else {
+                pieces.push(Piece::new(vec![point, first_corner, second_corner], 0.0));
+            }
+        }
+        self.fill_pieces(&pieces, color);
+    }
+
+    // Plot the union of the pieces, each pixel once with the largest coverage any piece gives it
+    fn fill_pieces(&mut self, pieces: &[Piece], color: Color) {
+        let mut left = i32::MAX;
+        let mut top = i32::MAX;
+        let mut right = i32::MIN;
+        let mut bottom = i32::MIN;
+        for piece in pieces.iter() {
+            let reach = piece.rounding + 1.0;
+            for point in piece.points.iter() {
+                left = left.min((point.x - reach).floor() as i32);
+                top = top.min((point.y - reach).floor() as i32);
+                right = right.max((point.x + reach).ceil() as i32);
+                bottom = bottom.max((point.y + reach).ceil() as i32);
+            }
+        }
+        let (clip_left, clip_top, clip_right, clip_bottom) = self.clip.edges();
+        let (left, top) = (left.max(clip_left), top.max(clip_top));
+        let (right, bottom) = (right.min(clip_right), bottom.min(clip_bottom));
+        if left > right || top > bottom {
+            return;
+        }
+        let columns = (right - left + 1) as usize;
+        let mut coverage = vec![0.0f32; columns * (bottom - top + 1) as usize];
+
+        for piece in pieces.iter() {
+            let reach = piece.rounding + 1.0;
+            for y in top..=bottom {
+                let row = y as f32;
+                let (start, end) = match piece.row_reach(row - reach, row + reach) {
+                    Some(reach) => reach,
+                    None => continue,
+                };
+                let start = ((start - reach).floor() as i32).max(left);
+                let end = ((end + reach).ceil() as i32).min(right);
+                for x in start..=end {
+                    let distance = piece.signed_distance(FVec2D::new(x as f32, row));
+                    let covered = if self.antialiasing {
+                        (0.5 - distance).clamp(0.0, 1.0)
+                    } else if distance <= 0.0 {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                    let cell = &mut coverage[(y - top) as usize * columns + (x - left) as usize];
+                    *cell = cell.max(covered);
+                }
+            }
+        }
+
+        for (i, &covered) in coverage.iter().enumerate() {
+            let x = left + (i % columns) as i32;
+            let y = top + (i / columns) as i32;
+            self.plot_coverage(x, y, color, covered);
+        }
+    }
+
+    ///
+    /// Draws text with the built in 8x8 font
+    /// position: Top left corner of the first character
+    /// text: Characters to draw, new lines start a new row. Characters outside of ASCII are left blank
+    /// color: Pixel color
+    /// scale: Size of each font pixel, 1 draws 8x8 characters
+    ///
+    pub fn text(&mut self, position: Point2D, text: &str, color: Color, scale: i32) {
+        for (row, line) in text.split('\n').enumerate() {
+            let top = position.y + row as i32 * font::GLYPH_SIZE * scale;
+            for (column, character) in line.chars().enumerate() {
+                let rows = match font::glyph(character) {
+                    Some(rows) => rows,
+                    None => continue,
+                };
+                let left = position.x + column as i32 * font::GLYPH_SIZE * scale;
+                for (y, bits) in rows.iter().enumerate() {
+                    for x in 0..font::GLYPH_SIZE {
+                        if bits & (1 << x) == 0 {
+                            continue;
+                        }
+                        let (x, y) = (left + x * scale, top + y as i32 * scale);
+                        for dy in 0..scale {
+                            for dx in 0..scale {
+                                self.plot(x + dx, y + dy, color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Draws a sprite at its own size, see-through pixels are blended with the blend mode of the canvas
+    /// sprite: Image to draw
+    /// position: Where the top left corner of the sprite goes
+    ///
+    pub fn draw_sprite(&mut self, sprite: &Sprite, position: Point2D) {
+        let (width, height) = (sprite.width() as i32, sprite.height() as i32);
+        self.draw_sprite_region(sprite, sprite.bounds(), position, width, height);
+    }
+
+    ///
+    /// Draws a sprite stretched over a rectangle, picking the nearest sprite pixel for each canvas pixel
+    /// sprite: Image to draw
+    /// position: Top left corner of the rectangle
+    /// width, height: Size of the rectangle in pixels
+    ///
+    pub fn draw_sprite_scaled(
+        &mut self,
+        sprite: &Sprite,
+        position: Point2D,
+        width: i32,
+        height: i32,
+    ) {
+        self.draw_sprite_region(sprite, sprite.bounds(), position, width, height);
+    }
+
+    ///
+    /// Draws part of a sprite stretched over a rectangle, for drawing single images of a sprite sheet
+    /// sprite: Sprite sheet
+    /// region: Part of the sprite to draw
+    /// position: Top left corner of the rectangle
+    /// width, height: Size of the rectangle in pixels, the size of the region draws it unscaled
+    ///
+    pub fn draw_sprite_region(
+        &mut self,
+        sprite: &Sprite,
+        region: SpriteRect,
+        position: Point2D,
+        width: i32,
+        height: i32,
+    ) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        let step_x = region.width as f32 / width as f32;
+        let step_y = region.height as f32 / height as f32;
+        // only the part of the rectangle inside the clip rectangle
+        let (left, top, right, bottom) = self.clip.edges();
+        let columns = (left - position.x).max(0)..=(right - position.x).min(width - 1);
+        for y in (top - position.y).max(0)..=(bottom - position.y).min(height - 1) {
+            // sample the sprite under the center of each canvas pixel
+            let source_y = region.y as i32 + ((y as f32 + 0.5) * step_y) as i32;
+            for x in columns.clone() {
+                let source_x = region.x as i32 + ((x as f32 + 0.5) * step_x) as i32;
+                let color = sprite.get(source_x, source_y);
+                self.plot(position.x + x, position.y + y, color);
+            }
+        }
+    }
+
+    ///
+    /// Draws a sprite turned around its center, picking the nearest sprite pixel for each canvas pixel
+    /// sprite: Image to draw
+    /// center: Where the center of the sprite goes. Pixel x covers x to x + 1, so a sprite of even
+    ///         size centered on a whole number lines up with the pixels
+    /// angle: Radians to turn the sprite, clockwise on screen
+    /// scale: Size of each sprite pixel, 1 keeps its size
+    ///
+    pub fn draw_sprite_rotated(&mut self, sprite: &Sprite, center: FVec2D, angle: f32, scale: f32) {
+        if scale <= 0.0 {
+            return;
+        }
+        let half = FVec2D::new(sprite.width() as f32, sprite.height() as f32) / 2.0;
+        let (sin, cos) = angle.sin_cos();
+        // the turned sprite fits in a circle through its corners
+        let reach = half.length() * scale;
+        let (clip_left, clip_top, clip_right, clip_bottom) = self.clip.edges();
+        let left = ((center.x - reach).floor() as i32).max(clip_left);
+        let right = ((center.x + reach).ceil() as i32).min(clip_right);
+        let top = ((center.y - reach).floor() as i32).max(clip_top);
+        let bottom = ((center.y + reach).ceil() as i32).min(clip_bottom);
+        for y in top..=bottom {
+            for x in left..=right {
+                // turn the center of each canvas pixel back into the sprite
+                let offset = (FVec2D::new(x as f32 + 0.5, y as f32 + 0.5) - center) / scale;
+                let source = FVec2D::new(
+                    offset.x * cos + offset.y * sin,
+                    -offset.x * sin + offset.y * cos,
+                ) + half;
+                if source.x < 0.0 || source.y < 0.0 {
+                    continue;
+                }
+                let color = sprite.get(source.x as i32, source.y as i32);
+                self.plot(x, y, color);
+            }
+        }
+    }
+}
+
+// Convex polygon grown by a radius: a point grown into a circle, a segment into a capsule
+// or a polygon into itself with rounded corners. Thick lines are built out of these.
+struct Piece {
+    points: Vec<FVec2D>,
+    rounding: f32,
+}
+
+impl Piece {
+    fn new(points: Vec<FVec2D>, rounding: f32) -> Self {
+        Self { points, rounding }
+    }
+
+    // Edges of the polygon, a single point or segment is its own edge
+    fn edges(&self) -> impl Iterator<Item = (FVec2D, FVec2D)> + '_ {
+        let count = self.points.len();
+        let edges = if count == 2 { 1 } else { count };
+        (0..edges).map(move |i| (self.points[i], self.points[(i + 1) % count]))
+    }
+
+    // Distance of point from the outline, negative inside
+    fn signed_distance(&self, point: FVec2D) -> f32 {
+        let mut closest = f32::INFINITY;
+        let (mut left_of, mut right_of) = (false, false);
+        for (start, end) in self.edges() {
+            let edge = end - start;
+            let length = edge.squared_length();
+            let t = if length == 0.0 {
+                0.0
+            } else {
+                (FVec2D::dot(point - start, edge) / length).clamp(0.0, 1.0)
+            };
+            closest = closest.min((start + edge * t - point).length());
+            let side = FVec2D::cross(edge, point - start);
+            left_of |= side > 0.0;
+            right_of |= side < 0.0;
+        }
+        let inside = self.points.len() >= 3 && !(left_of && right_of);
+        if inside {
+            -closest - self.rounding
+        } else {
+            closest - self.rounding
+        }
+    }
+
+    // Leftmost and rightmost x of the polygon between the rows low and high
+    fn row_reach(&self, low: f32, high: f32) -> Option<(f32, f32)> {
+        let mut reach: Option<(f32, f32)> = None;
+        let mut include = |x: f32| {
+            reach = Some(reach.map_or((x, x), |(start, end)| (start.min(x), end.max(x))));
+        };
+        for (start, end) in self.edges() {
+            let rise = end.y - start.y;
+            if rise == 0.0 {
+                if start.y >= low && start.y <= high {
+                    include(start.x);
+                    include(end.x);
+                }
+                continue;
+            }
+            let (t0, t1) = ((low - start.y) / rise, (high - start.y) / rise);
+            let (t0, t1) = (t0.min(t1).max(0.0), t0.max(t1).min(1.0));
+            if t0 <= t1 {
+                include(start.x + (end.x - start.x) * t0);
+                include(start.x + (end.x - start.x) * t1);
+            }
+        }
+        reach
     }
 }
@@ -0,0 +1,172 @@
+use crate::math::vector::Vector2D;
+
+// Cutting lines and polygons down to a rectangle before they are rasterized, so shapes reaching
+// far past the canvas cost no more than the part of them that can be seen.
+
+///
+/// Rectangle of pixels drawing is confined to, see Canvas::set_clip
+///     x, y -> top left corner, it may lie off the canvas
+///     width, height -> size in pixels
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ClipRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ClipRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Pixels inside both rectangles, an empty rectangle when they do not overlap
+    pub fn intersection(&self, other: ClipRect) -> ClipRect {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x as i64 + self.width as i64).min(other.x as i64 + other.width as i64);
+        let bottom = (self.y as i64 + self.height as i64).min(other.y as i64 + other.height as i64);
+        ClipRect::new(
+            left,
+            top,
+            (right - left as i64).max(0) as u32,
+            (bottom - top as i64).max(0) as u32,
+        )
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && (x as i64) < self.x as i64 + self.width as i64
+            && (y as i64) < self.y as i64 + self.height as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    // First and last column and row inside, the last ones come before the first when empty
+    pub(crate) fn edges(&self) -> (i32, i32, i32, i32) {
+        let last = |start: i32, size: u32| (start as i64 + size as i64 - 1).min(i32::MAX as i64);
+        (
+            self.x,
+            self.y,
+            last(self.x, self.width) as i32,
+            last(self.y, self.height) as i32,
+        )
+    }
+
+    // Area covered by the pixel centers inside, grown by margin on every side
+    pub(crate) fn bounds(&self, margin: f64) -> Bounds {
+        let (left, top, right, bottom) = self.edges();
+        Bounds {
+            left: left as f64 - margin,
+            top: top as f64 - margin,
+            right: right as f64 + margin,
+            bottom: bottom as f64 + margin,
+        }
+    }
+}
+
+// Rectangle lines and polygons are clipped to, its edges are inside
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Bounds {
+    pub(crate) left: f64,
+    pub(crate) top: f64,
+    pub(crate) right: f64,
+    pub(crate) bottom: f64,
+}
+
+impl Bounds {
+    // Same area with x and y swapped, for lines walked along y
+    pub(crate) fn transposed(&self) -> Bounds {
+        Bounds {
+            left: self.top,
+            top: self.left,
+            right: self.bottom,
+            bottom: self.right,
+        }
+    }
+
+    // Each edge as the outward normal and the furthest a point may go along it
+    fn planes(&self) -> [(Vector2D<f64>, f64); 4] {
+        [
+            (Vector2D::new(-1.0, 0.0), -self.left),
+            (Vector2D::new(1.0, 0.0), self.right),
+            (Vector2D::new(0.0, -1.0), -self.top),
+            (Vector2D::new(0.0, 1.0), self.bottom),
+        ]
+    }
+}
+
+///
+/// Part of the segment from start to end inside the bounds, as the fractions of the way
+/// from start to end where it enters and leaves them. None when the segment misses the bounds.
+/// https://en.wikipedia.org/wiki/Liang%E2%80%93Barsky_algorithm
+///
+pub(crate) fn clip_line(
+    start: Vector2D<f64>,
+    end: Vector2D<f64>,
+    bounds: Bounds,
+) -> Option<(f64, f64)> {
+    let delta = end - start;
+    let (mut enter, mut leave) = (0.0, 1.0);
+    for (normal, limit) in bounds.planes().iter() {
+        // how fast the segment heads out through this edge and how far inside start is
+        let outward = Vector2D::dot(delta, *normal);
+        let room = limit - Vector2D::dot(start, *normal);
+        if outward == 0.0 {
+            if room < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = room / outward;
+        if outward < 0.0 {
+            enter = t.max(enter);
+        } else {
+            leave = t.min(leave);
+        }
+        if enter > leave {
+            return None;
+        }
+    }
+    Some((enter, leave))
+}
+
+///
+/// Polygon cut down to the bounds one edge at a time, the outline may cross itself.
+/// Parts of the outline outside the bounds are replaced by runs along their edges, which
+/// cover no area, so every point inside the bounds is wound around as often as before.
+/// https://en.wikipedia.org/wiki/Sutherland%E2%80%93Hodgman_algorithm
+///
+pub(crate) fn clip_polygon(vertices: &[Vector2D<f64>], bounds: Bounds) -> Vec<Vector2D<f64>> {
+    let mut polygon = vertices.to_vec();
+    let mut clipped = Vec::with_capacity(polygon.len() + 4);
+    for (normal, limit) in bounds.planes().iter() {
+        // how far past the edge a point is, inside when not positive
+        let past = |point: Vector2D<f64>| Vector2D::dot(point, *normal) - limit;
+        clipped.clear();
+        for (i, &start) in polygon.iter().enumerate() {
+            let end = polygon[(i + 1) % polygon.len()];
+            let (start_past, end_past) = (past(start), past(end));
+            if start_past <= 0.0 {
+                clipped.push(start);
+            }
+            if (start_past <= 0.0) != (end_past <= 0.0) {
+                clipped.push(start + (end - start) * (start_past / (start_past - end_past)));
+            }
+        }
+        std::mem::swap(&mut polygon, &mut clipped);
+        if polygon.is_empty() {
+            break;
+        }
+    }
+    polygon
+}
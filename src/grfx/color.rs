@@ -9,6 +9,23 @@ use std::ops::SubAssign;
 #[derive(Default, Debug, Copy, Clone, PartialEq)]
 pub struct Color(u8, u8, u8, u8);
 
+///
+/// How a color drawn on top of a pixel combines with the color already there.
+/// The new color is always weighted by its alpha, a transparent color leaves the pixel as it is.
+///
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum BlendMode {
+    /// Covers the pixel, opaque colors replace it
+    #[default]
+    SourceOver,
+    /// Adds the colors together, overlapping colors get brighter
+    Additive,
+    /// Multiplies the colors, overlapping colors get darker
+    Multiply,
+    /// Inverts, multiplies and inverts again, overlapping colors get lighter
+    Screen,
+}
+
 /// Handy color definitions
 pub const BLACK: Color = Color(0, 0, 0, 255);
 pub const BLUE: Color = Color(0, 0, 255, 255);
@@ -62,6 +79,60 @@ impl Color {
     pub fn as_bytes(&self) -> [u8; 4] {
         [self.0, self.1, self.2, self.3]
     }
+
+    ///
+    /// Draw this color over the destination color
+    ///     destination -> color already in place
+    ///     mode -> how the colors combine
+    ///
+    /// Colors are not premultiplied. Where the destination is see through, the new color
+    /// shows through as it is instead of being mixed (as in the W3C compositing rules).
+    ///
+    pub fn blend(self, destination: Color, mode: BlendMode) -> Color {
+        if self.3 == 255 && mode == BlendMode::SourceOver {
+            return self;
+        }
+        if self.3 == 0 {
+            return destination;
+        }
+        let alpha = self.3 as f32 / 255.0;
+        let behind = destination.3 as f32 / 255.0;
+        if mode == BlendMode::Additive {
+            // both colors weighted by their alpha and added up, as the plus-lighter operator
+            let covered = (alpha + behind).min(1.0);
+            let add = |source: u8, destination: u8| {
+                let sum = (source as f32 * alpha + destination as f32 * behind).min(255.0);
+                (sum / covered).min(255.0).round() as u8
+            };
+            return Self(
+                add(self.0, destination.0),
+                add(self.1, destination.1),
+                add(self.2, destination.2),
+                (covered * 255.0).round() as u8,
+            );
+        }
+        let mix = |source: u8, destination: u8| {
+            let (s, d) = (source as f32 / 255.0, destination as f32 / 255.0);
+            let mixed = match mode {
+                // additive colors were added up above
+                BlendMode::SourceOver | BlendMode::Additive => s,
+                BlendMode::Multiply => s * d,
+                BlendMode::Screen => s + d - s * d,
+            };
+            // see through destinations show the source as it is
+            let source = (1.0 - behind) * s + behind * mixed;
+            let covered = alpha + behind * (1.0 - alpha);
+            let channel = (source * alpha + d * behind * (1.0 - alpha)) / covered;
+            (channel * 255.0).round() as u8
+        };
+        let covered = alpha + behind * (1.0 - alpha);
+        Self(
+            mix(self.0, destination.0),
+            mix(self.1, destination.1),
+            mix(self.2, destination.2),
+            (covered * 255.0).round() as u8,
+        )
+    }
 }
 
 /// Operator +
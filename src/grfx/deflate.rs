@@ -0,0 +1,449 @@
+use std::io::{Error, ErrorKind, Result};
+
+// zlib streams (RFC 1950) holding deflate data (RFC 1951), as used by PNG images.
+// Compression writes a single block of LZ77 matches with the fixed Huffman codes,
+// or stored blocks. Decompression reads every kind of block.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// order the code lengths of the code length alphabet are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: usize = 15;
+// candidates tried for each match, more finds longer matches but takes longer
+const MAX_CHAIN: usize = 64;
+
+/// Adler-32 checksum closing a zlib stream
+pub(crate) fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // the sums can run this many bytes before they have to be reduced
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk.iter() {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+///
+/// zlib stream of the data
+///     stored -> copy the data as it is instead of compressing it
+///
+pub(crate) fn compress(data: &[u8], stored: bool) -> Vec<u8> {
+    let mut output = BitWriter::default();
+    if stored {
+        output.bytes.extend_from_slice(&[0x78, 0x01]);
+        let blocks = data.chunks(65535).collect::<Vec<_>>();
+        if blocks.is_empty() {
+            output.write(1, 1);
+            output.write(0, 2);
+            output.align();
+            output.bytes.extend_from_slice(&[0x00, 0x00, 0xFF, 0xFF]);
+        }
+        for (i, block) in blocks.iter().enumerate() {
+            output.write((i + 1 == blocks.len()) as u32, 1);
+            output.write(0, 2);
+            output.align();
+            let length = block.len() as u16;
+            output.bytes.extend_from_slice(&length.to_le_bytes());
+            output.bytes.extend_from_slice(&(!length).to_le_bytes());
+            output.bytes.extend_from_slice(block);
+        }
+    } else {
+        output.bytes.extend_from_slice(&[0x78, 0x9C]);
+        output.write(1, 1);
+        output.write(1, 2);
+        compress_fixed(data, &mut output);
+        output.align();
+    }
+    output.bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    output.bytes
+}
+
+// Literals and LZ77 matches found through chains of earlier positions with the same 3 bytes
+fn compress_fixed(data: &[u8], output: &mut BitWriter) {
+    let mut chains = Chains {
+        head: vec![usize::MAX; 1 << HASH_BITS],
+        previous: vec![usize::MAX; WINDOW],
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let longest = MAX_MATCH.min(data.len() - i);
+            let mut candidate = chains.head[hash(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(data[i..i + longest].iter())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == longest {
+                        break;
+                    }
+                }
+                let next = chains.previous[candidate % WINDOW];
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            let code = LENGTH_BASE
+                .iter()
+                .rposition(|&base| base as usize <= best_length)
+                .unwrap_or(0);
+            output.write_fixed_literal(257 + code as u16);
+            output.write(
+                (best_length - LENGTH_BASE[code] as usize) as u32,
+                LENGTH_EXTRA[code] as u32,
+            );
+            let code = DISTANCE_BASE
+                .iter()
+                .rposition(|&base| base as usize <= best_distance)
+                .unwrap_or(0);
+            output.write_huffman(code as u32, 5);
+            output.write(
+                (best_distance - DISTANCE_BASE[code] as usize) as u32,
+                DISTANCE_EXTRA[code] as u32,
+            );
+            for position in i..i + best_length {
+                chains.insert(data, position);
+            }
+            i += best_length;
+        } else {
+            output.write_fixed_literal(data[i] as u16);
+            chains.insert(data, i);
+            i += 1;
+        }
+    }
+    output.write_fixed_literal(256);
+}
+
+// Positions seen so far, chained by the hash of the 3 bytes starting there
+struct Chains {
+    // latest position of each hash
+    head: Vec<usize>,
+    // position before each position in the window with the same hash
+    previous: Vec<usize>,
+}
+
+impl Chains {
+    fn insert(&mut self, data: &[u8], i: usize) {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            self.previous[i % WINDOW] = self.head[h];
+            self.head[h] = i;
+        }
+    }
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize)
+        & ((1 << HASH_BITS) - 1)
+}
+
+/// Data held in a zlib stream, checked against its Adler-32 checksum
+pub(crate) fn decompress(stream: &[u8]) -> Result<Vec<u8>> {
+    if stream.len() < 6 {
+        return Err(invalid("zlib stream is too short"));
+    }
+    let (method, flags) = (stream[0], stream[1]);
+    if method & 0x0F != 8 || (method as u16 * 256 + flags as u16) % 31 != 0 {
+        return Err(invalid("not a deflate zlib stream"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(invalid("zlib preset dictionaries are not supported"));
+    }
+    let mut input = BitReader::new(&stream[2..]);
+    let data = inflate(&mut input)?;
+    let end = 2 + input.position;
+    let checksum = stream
+        .get(end..end + 4)
+        .ok_or_else(|| invalid("zlib stream is missing its checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&data) {
+        return Err(invalid("zlib checksum does not match"));
+    }
+    Ok(data)
+}
+
+fn inflate(input: &mut BitReader) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let length = input.bits(16)?;
+                if input.bits(16)? != !length & 0xFFFF {
+                    return Err(invalid("stored block length does not match its complement"));
+                }
+                for _ in 0..length {
+                    output.push(input.bits(8)? as u8);
+                }
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                for (symbol, length) in lengths.iter_mut().enumerate() {
+                    *length = match symbol {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8,
+                    };
+                }
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(input, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(input)?;
+                inflate_block(input, &mut output, &literals, &distances)?;
+            }
+            _ => return Err(invalid("unknown deflate block type")),
+        }
+        if last {
+            input.align();
+            return Ok(output);
+        }
+    }
+}
+
+// Huffman codes stored at the start of a dynamic block, themselves Huffman coded
+fn dynamic_codes(input: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_length_count = input.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[symbol] = input.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_lengths.decode(input)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(&previous) => (previous, 3 + input.bits(2)?),
+                None => return Err(invalid("length repeated before the first length")),
+            },
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("code lengths run past the end of the codes"));
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("block has no end of block code"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+fn inflate_block(
+    input: &mut BitReader,
+    output: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(input)? as usize;
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let code = symbol - 257;
+        if code >= LENGTH_BASE.len() {
+            return Err(invalid("unknown length code"));
+        }
+        let length = LENGTH_BASE[code] as usize + input.bits(LENGTH_EXTRA[code] as u32)? as usize;
+        let code = distances.decode(input)? as usize;
+        if code >= DISTANCE_BASE.len() {
+            return Err(invalid("unknown distance code"));
+        }
+        let distance =
+            DISTANCE_BASE[code] as usize + input.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+        if distance > output.len() {
+            return Err(invalid("match reaches back before the start of the data"));
+        }
+        // byte by byte, matches may overlap the bytes they copy
+        let start = output.len() - distance;
+        for i in 0..length {
+            output.push(output[start + i]);
+        }
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// Canonical Huffman code, decoded a bit at a time as in zlib's puff
+struct Huffman {
+    // number of codes of each length
+    counts: [u16; 16],
+    // symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths.iter() {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, input: &mut BitReader) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in self.counts.iter().skip(1) {
+            code |= input.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+// Reads bits starting from the lowest bit of each byte
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            bit: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self
+                .bytes
+                .get(self.position)
+                .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "deflate data ends early"))?;
+            value |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    // skip to the start of the next byte
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+// Writes bits starting from the lowest bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are stored starting from their highest bit
+    fn write_huffman(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn write_fixed_literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.write_huffman(0x30 + symbol, 8),
+            144..=255 => self.write_huffman(0x190 + symbol - 144, 9),
+            256..=279 => self.write_huffman(symbol - 256, 7),
+            _ => self.write_huffman(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    // pad to the end of the byte with zeros
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
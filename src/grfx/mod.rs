@@ -1,3 +1,13 @@
 pub mod canvas;
+pub mod clip;
 pub mod color;
+mod deflate;
+pub mod font;
+pub mod netpbm;
+pub mod png;
 pub mod render;
+#[cfg(test)]
+mod snapshot;
+pub mod sprite;
+#[cfg(test)]
+mod test;
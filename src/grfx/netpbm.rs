@@ -0,0 +1,248 @@
+use crate::grfx::color::Color;
+use std::io::{Error, ErrorKind, Result};
+
+// Netpbm images: PPM holds the red, green and blue of every pixel,
+// PAM holds alpha as well. Both are simple enough to write and read without an image library
+// and are opened by most image viewers and editors.
+// http://netpbm.sourceforge.net/doc/ppm.html
+// http://netpbm.sourceforge.net/doc/pam.html
+
+/// How the samples of a PPM image are stored
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum PpmFormat {
+    /// P6, one byte per sample
+    #[default]
+    Binary,
+    /// P3, samples written out as decimal numbers, easy to read and diff
+    Plain,
+}
+
+/// PPM image of the pixels, alpha is dropped
+pub fn encode_ppm(width: u32, height: u32, pixels: &[Color], format: PpmFormat) -> Vec<u8> {
+    match format {
+        PpmFormat::Binary => {
+            let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+            for pixel in pixels.iter() {
+                bytes.extend_from_slice(&pixel.as_bytes()[..3]);
+            }
+            bytes
+        }
+        PpmFormat::Plain => {
+            let mut text = format!("P3\n{} {}\n255\n", width, height);
+            for pixel in pixels.iter() {
+                text.push_str(&pixel.to_string());
+            }
+            text.into_bytes()
+        }
+    }
+}
+
+/// PAM image of the pixels, alpha included
+pub fn encode_pam(width: u32, height: u32, pixels: &[Color]) -> Vec<u8> {
+    let mut bytes = format!(
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        width, height
+    )
+    .into_bytes();
+    for pixel in pixels.iter() {
+        bytes.extend_from_slice(&pixel.as_bytes());
+    }
+    bytes
+}
+
+///
+/// Width, height and pixels of a P3 or P6 image. Samples larger than a byte are scaled down,
+/// pixels are opaque.
+///
+pub fn decode_ppm(bytes: &[u8]) -> Result<(u32, u32, Vec<Color>)> {
+    let mut header = Header::new(bytes);
+    let plain = match header.token()? {
+        "P3" => true,
+        "P6" => false,
+        magic => return Err(invalid(&format!("not a P3 or P6 image: {}", magic))),
+    };
+    let width = header.number()?;
+    let height = header.number()?;
+    let max_value = header.number()?;
+    let count = sample_count(width, height, 3, max_value)?;
+
+    let samples = if plain {
+        (0..count)
+            .map(|_| Ok(scale(header.number()?, max_value)))
+            .collect::<Result<Vec<u8>>>()?
+    } else {
+        header.single_whitespace()?;
+        binary_samples(header.rest(), count, max_value)?
+    };
+    let pixels = samples
+        .chunks(3)
+        .map(|rgb| Color::rgb(rgb[0], rgb[1], rgb[2]))
+        .collect();
+    Ok((width, height, pixels))
+}
+
+///
+/// Width, height and pixels of a P7 image with a depth of 1 (gray), 2 (gray and alpha),
+/// 3 (RGB) or 4 (RGB and alpha). Samples larger than a byte are scaled down.
+///
+pub fn decode_pam(bytes: &[u8]) -> Result<(u32, u32, Vec<Color>)> {
+    let mut header = Header::new(bytes);
+    if header.token()? != "P7" {
+        return Err(invalid("not a P7 image"));
+    }
+    let (mut width, mut height, mut depth, mut max_value) = (None, None, None, None);
+    loop {
+        match header.token()? {
+            "WIDTH" => width = Some(header.number()?),
+            "HEIGHT" => height = Some(header.number()?),
+            "DEPTH" => depth = Some(header.number()?),
+            "MAXVAL" => max_value = Some(header.number()?),
+            // the depth is enough to tell the layout apart
+            "TUPLTYPE" => header.skip_line(),
+            "ENDHDR" => break,
+            field => return Err(invalid(&format!("unknown PAM header field {}", field))),
+        }
+    }
+    let (width, height, depth, max_value) = match (width, height, depth, max_value) {
+        (Some(width), Some(height), Some(depth), Some(max_value)) => {
+            (width, height, depth, max_value)
+        }
+        _ => return Err(invalid("PAM header is missing a field")),
+    };
+    if !(1..=4).contains(&depth) {
+        return Err(invalid(&format!("unsupported PAM depth {}", depth)));
+    }
+    let count = sample_count(width, height, depth, max_value)?;
+    header.single_whitespace()?;
+
+    let samples = binary_samples(header.rest(), count, max_value)?;
+    let pixels = samples
+        .chunks(depth as usize)
+        .map(|tuple| match tuple {
+            [gray] => Color::rgb(*gray, *gray, *gray),
+            [gray, alpha] => Color::rgba(*gray, *gray, *gray, *alpha),
+            [r, g, b] => Color::rgb(*r, *g, *b),
+            [r, g, b, alpha] => Color::rgba(*r, *g, *b, *alpha),
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// Number of samples, depth for every pixel, refusing sizes that overflow or samples that
+// could not be scaled
+fn sample_count(width: u32, height: u32, depth: u32, max_value: u32) -> Result<usize> {
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid(&format!("unsupported maximum value {}", max_value)));
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|count| count.checked_mul(depth as usize))
+        .ok_or_else(|| invalid("image too large"))
+}
+
+// Sample scaled from 0..=max_value to 0..=255
+fn scale(sample: u32, max_value: u32) -> u8 {
+    if max_value == 255 {
+        return sample.min(255) as u8;
+    }
+    ((sample.min(max_value) * 255 + max_value / 2) / max_value) as u8
+}
+
+// Samples of one byte, or two bytes with the most significant first when max_value needs them
+fn binary_samples(bytes: &[u8], count: usize, max_value: u32) -> Result<Vec<u8>> {
+    let size = if max_value > 255 { 2 } else { 1 };
+    let length = count
+        .checked_mul(size)
+        .ok_or_else(|| invalid("image too large"))?;
+    if bytes.len() < length {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "image data is shorter than its header says",
+        ));
+    }
+    Ok(bytes[..length]
+        .chunks(size)
+        .map(|sample| {
+            let value = sample
+                .iter()
+                .fold(0, |value, &byte| value << 8 | byte as u32);
+            scale(value, max_value)
+        })
+        .collect())
+}
+
+// Reads the text header, where tokens are separated by whitespace and # starts a comment
+struct Header<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Header<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn token(&mut self) -> Result<&'a str> {
+        while let Some(&byte) = self.bytes.get(self.position) {
+            if byte == b'#' {
+                self.skip_line();
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|byte| !byte.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "image header ends early",
+            ));
+        }
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| invalid("image header is not text"))
+    }
+
+    fn number(&mut self) -> Result<u32> {
+        let token = self.token()?;
+        token
+            .parse()
+            .map_err(|_| invalid(&format!("expected a number, found {}", token)))
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(&byte) = self.bytes.get(self.position) {
+            self.position += 1;
+            if byte == b'\n' {
+                break;
+            }
+        }
+    }
+
+    // Binary data starts after the single whitespace ending the header
+    fn single_whitespace(&mut self) -> Result<()> {
+        match self.bytes.get(self.position) {
+            Some(byte) if byte.is_ascii_whitespace() => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(invalid("image header does not end in whitespace")),
+        }
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.position..]
+    }
+}
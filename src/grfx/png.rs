@@ -0,0 +1,267 @@
+use crate::grfx::color::Color;
+use crate::grfx::deflate;
+use std::io::{Error, ErrorKind, Result};
+
+// PNG images without an image library. Images are written as 8 bit RGBA,
+// 8 bit RGB and RGBA images that are not interlaced can be read back.
+// http://www.libpng.org/pub/png/spec/1.2/PNG-Contents.html
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+const RGB: u8 = 2;
+const RGBA: u8 = 6;
+
+/// How the image data of a PNG image is stored
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum PngCompression {
+    /// Deflate compressed, small files
+    #[default]
+    Deflate,
+    /// Stored as it is, large files but quick to write
+    Stored,
+}
+
+/// PNG image of the pixels as 8 bit RGBA
+pub fn encode_png(
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+    compression: PngCompression,
+) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per sample, RGBA, deflate, adaptive filtering, not interlaced
+    header.extend_from_slice(&[8, RGBA, 0, 0, 0]);
+
+    let row_size = width as usize * 4;
+    let samples: Vec<u8> = pixels.iter().flat_map(|pixel| pixel.as_bytes()).collect();
+    let mut filtered = Vec::with_capacity((row_size + 1) * height as usize);
+    let blank = vec![0; row_size];
+    for (y, row) in samples.chunks(row_size.max(1)).enumerate() {
+        let above = if y == 0 {
+            &blank[..]
+        } else {
+            &samples[(y - 1) * row_size..y * row_size]
+        };
+        filter_row(row, above, 4, &mut filtered);
+    }
+
+    let mut bytes = SIGNATURE.to_vec();
+    write_chunk(&mut bytes, b"IHDR", &header);
+    write_chunk(
+        &mut bytes,
+        b"IDAT",
+        &deflate::compress(&filtered, compression == PngCompression::Stored),
+    );
+    write_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+/// Width, height and pixels of an 8 bit RGB or RGBA PNG image that is not interlaced
+pub fn decode_png(bytes: &[u8]) -> Result<(u32, u32, Vec<Color>)> {
+    if bytes.len() < SIGNATURE.len() || bytes[..SIGNATURE.len()] != SIGNATURE {
+        return Err(invalid("not a PNG image"));
+    }
+    let mut header = None;
+    let mut data = Vec::new();
+    let mut position = SIGNATURE.len();
+    loop {
+        let (kind, contents) = read_chunk(bytes, &mut position)?;
+        match &kind {
+            b"IHDR" => header = Some(contents),
+            b"IDAT" => data.extend_from_slice(contents),
+            b"IEND" => break,
+            // a palette is only a suggestion for RGB images
+            b"PLTE" => (),
+            _ if kind[0].is_ascii_uppercase() => {
+                return Err(invalid(&format!(
+                    "unsupported critical chunk {}",
+                    String::from_utf8_lossy(&kind)
+                )))
+            }
+            _ => (),
+        }
+    }
+
+    let header = match header {
+        Some(header) if header.len() == 13 => header,
+        _ => return Err(invalid("PNG image has no valid header")),
+    };
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let channels = match (depth, color_type) {
+        (8, RGB) => 3,
+        (8, RGBA) => 4,
+        _ => {
+            return Err(unsupported(&format!(
+                "only 8 bit RGB and RGBA images are supported, not bit depth {} color type {}",
+                depth, color_type
+            )))
+        }
+    };
+    if header[10] != 0 || header[11] != 0 {
+        return Err(invalid("unknown compression or filter method"));
+    }
+    if interlace != 0 {
+        return Err(unsupported("interlaced images are not supported"));
+    }
+
+    let filtered = deflate::decompress(&data)?;
+    let row_size = (width as usize)
+        .checked_mul(channels)
+        .ok_or_else(|| invalid("image too large"))?;
+    let expected = (row_size + 1)
+        .checked_mul(height as usize)
+        .ok_or_else(|| invalid("image too large"))?;
+    if filtered.len() < expected {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "image data is shorter than its header says",
+        ));
+    }
+    let mut samples = vec![0; row_size * height as usize];
+    for (y, row) in filtered[..expected].chunks(row_size + 1).enumerate() {
+        let (done, rest) = samples.split_at_mut(y * row_size);
+        let above = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * row_size..])
+        };
+        unfilter_row(row[0], &row[1..], above, channels, &mut rest[..row_size])?;
+    }
+
+    let pixels = samples
+        .chunks(channels)
+        .map(|sample| match sample {
+            [r, g, b] => Color::rgb(*r, *g, *b),
+            [r, g, b, alpha] => Color::rgba(*r, *g, *b, *alpha),
+            _ => unreachable!(),
+        })
+        .collect();
+    Ok((width, height, pixels))
+}
+
+/// CRC-32 closing each chunk
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes.iter() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn unsupported(message: &str) -> Error {
+    Error::new(ErrorKind::Unsupported, message)
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], contents: &[u8]) {
+    bytes.extend_from_slice(&(contents.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(contents);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Kind and contents of the chunk at position, moving position past it
+fn read_chunk<'a>(bytes: &'a [u8], position: &mut usize) -> Result<([u8; 4], &'a [u8])> {
+    let ends_early = || Error::new(ErrorKind::UnexpectedEof, "PNG image ends early");
+    let start = *position;
+    let length = bytes.get(start..start + 4).ok_or_else(ends_early)?;
+    let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+    let end = start + 8 + length;
+    let chunk = bytes.get(start + 4..end).ok_or_else(ends_early)?;
+    let crc = bytes.get(end..end + 4).ok_or_else(ends_early)?;
+    if crc32(chunk) != u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+        return Err(invalid("chunk CRC does not match"));
+    }
+    *position = end + 4;
+    Ok(([chunk[0], chunk[1], chunk[2], chunk[3]], &chunk[4..]))
+}
+
+// Predicts a sample from the ones left, above and above left of it
+fn paeth(left: u8, above: u8, above_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - above_left as i16;
+    let (to_left, to_above, to_above_left) = (
+        (estimate - left as i16).abs(),
+        (estimate - above as i16).abs(),
+        (estimate - above_left as i16).abs(),
+    );
+    if to_left <= to_above && to_left <= to_above_left {
+        left
+    } else if to_above <= to_above_left {
+        above
+    } else {
+        above_left
+    }
+}
+
+// Prediction for sample i of a row by each of the five filters
+fn predict(filter: u8, row: &[u8], above: &[u8], i: usize, channels: usize) -> u8 {
+    let left = if i >= channels { row[i - channels] } else { 0 };
+    let above_left = if i >= channels {
+        above[i - channels]
+    } else {
+        0
+    };
+    match filter {
+        1 => left,
+        2 => above[i],
+        3 => ((left as u16 + above[i] as u16) / 2) as u8,
+        4 => paeth(left, above[i], above_left),
+        _ => 0,
+    }
+}
+
+// Append the row filtered with the filter leaving the smallest differences,
+// a heuristic from the PNG specification that usually compresses best
+fn filter_row(row: &[u8], above: &[u8], channels: usize, output: &mut Vec<u8>) {
+    let filtered = |filter: u8| -> Vec<u8> {
+        (0..row.len())
+            .map(|i| row[i].wrapping_sub(predict(filter, row, above, i, channels)))
+            .collect()
+    };
+    let cost = |samples: &[u8]| -> u32 {
+        samples
+            .iter()
+            .map(|&sample| (sample as i8).unsigned_abs() as u32)
+            .sum()
+    };
+    let (filter, samples) = (0..5)
+        .map(|filter| (filter, filtered(filter)))
+        .min_by_key(|(_, samples)| cost(samples))
+        .unwrap_or((0, Vec::new()));
+    output.push(filter);
+    output.extend_from_slice(&samples);
+}
+
+// Undo the filter of a row, given the row above already unfiltered
+fn unfilter_row(
+    filter: u8,
+    filtered: &[u8],
+    above: Option<&[u8]>,
+    channels: usize,
+    row: &mut [u8],
+) -> Result<()> {
+    if filter > 4 {
+        return Err(invalid(&format!("unknown filter type {}", filter)));
+    }
+    let blank = vec![0; row.len()];
+    let above = above.unwrap_or(&blank);
+    for i in 0..row.len() {
+        row[i] = filtered[i].wrapping_add(predict(filter, row, above, i, channels));
+    }
+    Ok(())
+}
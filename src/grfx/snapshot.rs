@@ -0,0 +1,267 @@
+use crate::grfx::canvas::{Canvas, FillRule, LineCap, LineJoin};
+use crate::grfx::clip::ClipRect;
+use crate::grfx::color;
+use crate::grfx::color::{BlendMode, Color};
+use crate::grfx::png::PngCompression;
+use crate::grfx::sprite::Sprite;
+use crate::math::vector::{FVec2D, Point2D};
+use std::path::{Path, PathBuf};
+
+// Snapshot tests: each primitive is drawn into a small canvas and compared pixel for pixel
+// with a reference image checked in to src/grfx/golden. When they differ the test fails and
+// leaves the drawn image and a diff image in target/snapshots, where matching pixels are
+// dimmed and differing pixels are red.
+//
+// After a deliberate change to a primitive, look at the new images and accept them with
+//     UPDATE_SNAPSHOTS=1 cargo test snapshot
+
+const SIZE: u32 = 32;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/grfx/golden")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target/snapshots")
+}
+
+fn assert_snapshot(name: &str, canvas: &Canvas) {
+    let reference = golden_dir().join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        canvas
+            .save_png(&reference, PngCompression::Deflate)
+            .unwrap();
+        return;
+    }
+    let expected = Canvas::load_png(&reference).unwrap_or_else(|error| {
+        panic!(
+            "no reference image {} ({}), run with UPDATE_SNAPSHOTS=1 to create it",
+            reference.display(),
+            error
+        )
+    });
+    if let Some(difference) = compare(&expected, canvas) {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual = output_dir().join(format!("{}.actual.png", name));
+        let diff = output_dir().join(format!("{}.diff.png", name));
+        canvas.save_png(&actual, PngCompression::Deflate).unwrap();
+        diff_image(&expected, canvas)
+            .save_png(&diff, PngCompression::Deflate)
+            .unwrap();
+        panic!(
+            "{} does not match its reference image: {}\n  drawn: {}\n  diff: {}",
+            name,
+            difference,
+            actual.display(),
+            diff.display()
+        );
+    }
+}
+
+// What differs between the images, None when they are the same
+fn compare(expected: &Canvas, actual: &Canvas) -> Option<String> {
+    let (width, height) = (expected.width(), expected.height());
+    if (width, height) != (actual.width(), actual.height()) {
+        return Some(format!(
+            "size is {}x{} instead of {}x{}",
+            actual.width(),
+            actual.height(),
+            width,
+            height
+        ));
+    }
+    let differing: Vec<usize> = (0..expected.pixels.len())
+        .filter(|&i| expected.pixels[i] != actual.pixels[i])
+        .collect();
+    let first = *differing.first()?;
+    Some(format!(
+        "{} pixels differ, the first at ({}, {}) is {:?} instead of {:?}",
+        differing.len(),
+        first as u32 % width,
+        first as u32 / width,
+        actual.pixels[first],
+        expected.pixels[first]
+    ))
+}
+
+// Matching pixels dimmed to gray, differing pixels and pixels outside either image red
+fn diff_image(expected: &Canvas, actual: &Canvas) -> Canvas {
+    let width = expected.width().max(actual.width());
+    let height = expected.height().max(actual.height());
+    let pixel = |canvas: &Canvas, x: u32, y: u32| {
+        if x < canvas.width() && y < canvas.height() {
+            Some(canvas.pixels[(y * canvas.width() + x) as usize])
+        } else {
+            None
+        }
+    };
+    let mut diff = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = match (pixel(expected, x, y), pixel(actual, x, y)) {
+                (Some(expected), Some(actual)) if expected == actual => {
+                    let light = (actual.r() as u32 + actual.g() as u32 + actual.b() as u32) / 12;
+                    Color::rgb(light as u8, light as u8, light as u8)
+                }
+                _ => color::RED,
+            };
+            diff.plot(x as i32, y as i32, color);
+        }
+    }
+    diff
+}
+
+fn blank() -> Canvas {
+    Canvas::new(SIZE, SIZE)
+}
+
+#[test]
+fn snapshot_lines() {
+    let mut canvas = blank();
+    canvas.line(2, 2, 29, 2, color::WHITE);
+    canvas.line(2, 4, 2, 29, color::RED);
+    canvas.line(5, 5, 29, 29, color::GREEN);
+    canvas.line(5, 29, 29, 12, color::YELLOW);
+    canvas.line(8, 29, 14, 6, color::CYAN);
+    assert_snapshot("lines", &canvas);
+}
+
+#[test]
+fn snapshot_circles() {
+    let mut canvas = blank();
+    canvas.circle(Point2D::new(10, 10), 8, color::WHITE);
+    canvas.filled_circle(Point2D::new(21, 21), 9, color::BLUE);
+    canvas.filled_circle(Point2D::new(5, 26), 0, color::RED);
+    assert_snapshot("circles", &canvas);
+}
+
+#[test]
+fn snapshot_rectangles() {
+    let mut canvas = blank();
+    canvas.rectangle(Point2D::new(2, 2), 12, 8, color::WHITE);
+    canvas.filled_rectangle(Point2D::new(28, 12), -10, 14, color::GREEN);
+    canvas.filled_rectangle(Point2D::new(3, 14), 0, 0, color::RED);
+    assert_snapshot("rectangles", &canvas);
+}
+
+#[test]
+fn snapshot_triangles_and_polygons() {
+    let mut canvas = blank();
+    canvas.triangle(
+        Point2D::new(2, 2),
+        Point2D::new(14, 4),
+        Point2D::new(6, 13),
+        color::WHITE,
+    );
+    canvas.filled_triangle(
+        Point2D::new(17, 2),
+        Point2D::new(30, 12),
+        Point2D::new(18, 14),
+        color::MAGENTA,
+    );
+    canvas.regular_polygon(Point2D::new(3, 18), 6, 5, color::CYAN);
+    let star = [
+        Point2D::new(23, 16),
+        Point2D::new(29, 30),
+        Point2D::new(16, 21),
+        Point2D::new(30, 21),
+        Point2D::new(17, 30),
+    ];
+    canvas.filled_polygon(&star, FillRule::EvenOdd, color::YELLOW);
+    assert_snapshot("triangles_and_polygons", &canvas);
+}
+
+#[test]
+fn snapshot_smooth_shapes() {
+    let mut canvas = blank();
+    canvas.set_antialiasing(true);
+    canvas.line(2, 3, 29, 14, color::WHITE);
+    canvas.circle(Point2D::new(10, 22), 7, color::CYAN);
+    canvas.smooth_filled_circle(FVec2D::new(23.5, 22.25), 6.5, color::YELLOW);
+    assert_snapshot("smooth_shapes", &canvas);
+}
+
+#[test]
+fn snapshot_thick_lines() {
+    let mut canvas = blank();
+    let zigzag = [
+        FVec2D::new(4.0, 12.0),
+        FVec2D::new(12.0, 4.0),
+        FVec2D::new(20.0, 12.0),
+        FVec2D::new(28.0, 4.0),
+    ];
+    canvas.thick_polyline(&zigzag, 3.0, LineCap::Butt, LineJoin::Miter, color::WHITE);
+    canvas.thick_line(
+        FVec2D::new(6.0, 20.0),
+        FVec2D::new(26.0, 27.0),
+        6.0,
+        LineCap::Round,
+        color::GREEN,
+    );
+    assert_snapshot("thick_lines", &canvas);
+}
+
+#[test]
+fn snapshot_text() {
+    let mut canvas = blank();
+    canvas.text(Point2D::new(0, 0), "Hi!\n#3", color::WHITE, 1);
+    canvas.text(Point2D::new(8, 17), "g", color::YELLOW, 2);
+    assert_snapshot("text", &canvas);
+}
+
+#[test]
+fn snapshot_blending_and_sprites() {
+    let mut canvas = blank();
+    canvas.filled_rectangle(Point2D::new(0, 0), 31, 15, Color::rgb(40, 40, 120));
+    canvas.filled_circle(Point2D::new(10, 8), 7, Color::rgba(255, 0, 0, 128));
+    canvas.with_blend_mode(BlendMode::Additive, |canvas| {
+        canvas.filled_circle(Point2D::new(21, 8), 7, Color::rgba(0, 255, 0, 160))
+    });
+
+    let mut arrow = Sprite::new(8, 8);
+    for y in 0..8 {
+        for x in 0..=(y.min(7 - y)) {
+            arrow.set(x * 2, y, color::WHITE);
+            arrow.set(x * 2 + 1, y, Color::rgba(255, 255, 255, 100));
+        }
+    }
+    canvas.draw_sprite(&arrow, Point2D::new(2, 20));
+    canvas.draw_sprite_rotated(
+        &arrow,
+        FVec2D::new(22.0, 24.0),
+        std::f32::consts::FRAC_PI_4,
+        1.0,
+    );
+    assert_snapshot("blending_and_sprites", &canvas);
+}
+
+#[test]
+fn snapshot_clipping() {
+    let mut canvas = blank();
+    canvas.rectangle(Point2D::new(3, 3), 25, 12, color::GRAY);
+    canvas.with_clip(ClipRect::new(4, 4, 24, 11), |canvas| {
+        canvas.line(-1000, 40, 1000, -20, color::WHITE);
+        canvas.filled_circle(Point2D::new(26, 14), 6, color::RED);
+        canvas.text(Point2D::new(1, 6), "clip", color::YELLOW, 1);
+    });
+    canvas.set_antialiasing(true);
+    canvas.with_clip(ClipRect::new(0, 18, 32, 14), |canvas| {
+        let spikes = [
+            Point2D::new(-400, 31),
+            Point2D::new(10, 20),
+            Point2D::new(16, -300),
+            Point2D::new(22, 20),
+            Point2D::new(400, 31),
+        ];
+        canvas.filled_polygon(&spikes, FillRule::NonZero, color::BLUE);
+        canvas.thick_line(
+            FVec2D::new(-50.0, 40.0),
+            FVec2D::new(80.0, 10.0),
+            3.0,
+            LineCap::Round,
+            color::GREEN,
+        );
+    });
+    assert_snapshot("clipping", &canvas);
+}
@@ -0,0 +1,113 @@
+use crate::grfx::color::Color;
+use crate::grfx::png;
+use std::path::Path;
+
+///
+/// Image owned in memory that can be drawn onto a canvas, see Canvas::draw_sprite.
+/// Pixels are stored row by row from the top left corner, their alpha is
+/// honoured when the sprite is drawn.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    width: u32,
+    height: u32,
+    pub pixels: Vec<Color>,
+}
+
+///
+/// Rectangle of pixels in a sprite, used to pick single images out of a sprite sheet
+///     x, y -> top left corner
+///     width, height -> size in pixels
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl SpriteRect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl Sprite {
+    /// Fully transparent sprite
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width as usize * height as usize],
+        }
+    }
+
+    /// Sprite from pixels stored row by row, None when there are not exactly width * height of them
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Color>) -> Option<Self> {
+        if pixels.len() != width as usize * height as usize {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Sprite holding an 8 bit RGB or RGBA PNG image
+    pub fn load_png<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let (width, height, pixels) = png::decode_png(&std::fs::read(path)?)?;
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The whole sprite as a rectangle
+    pub fn bounds(&self) -> SpriteRect {
+        SpriteRect::new(0, 0, self.width, self.height)
+    }
+
+    /// Color of a pixel, transparent outside of the sprite
+    pub fn get(&self, x: i32, y: i32) -> Color {
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            self.pixels[y as usize * self.width as usize + x as usize]
+        } else {
+            Color::default()
+        }
+    }
+
+    /// Change the color of a pixel, pixels outside of the sprite are ignored
+    pub fn set(&mut self, x: i32, y: i32, color: Color) {
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            self.pixels[y as usize * self.width as usize + x as usize] = color;
+        }
+    }
+
+    /// Copy of part of the sprite, for cutting a sprite sheet into its images.
+    /// Parts of the rectangle outside of the sprite are transparent
+    pub fn region(&self, rect: SpriteRect) -> Sprite {
+        let mut region = Sprite::new(rect.width, rect.height);
+        for y in 0..rect.height as i32 {
+            for x in 0..rect.width as i32 {
+                region.set(x, y, self.get(rect.x as i32 + x, rect.y as i32 + y));
+            }
+        }
+        region
+    }
+}
@@ -0,0 +1,593 @@
+use crate::grfx::canvas::{Canvas, FillRule, LineCap, LineJoin};
+use crate::grfx::clip::ClipRect;
+use crate::grfx::color;
+use crate::grfx::color::{BlendMode, Color};
+use crate::grfx::deflate;
+use crate::grfx::font;
+use crate::grfx::netpbm;
+use crate::grfx::netpbm::PpmFormat;
+use crate::grfx::png;
+use crate::grfx::png::PngCompression;
+use crate::grfx::sprite::{Sprite, SpriteRect};
+use crate::math::vector::{FVec2D, Point2D};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::ErrorKind;
+
+#[test]
+fn opaque_source_over_replaces_the_pixel() {
+    let mut canvas = Canvas::new(4, 4);
+    canvas.clear(color::RED);
+    canvas.plot(1, 1, color::BLUE);
+    assert_eq!(canvas.pixels[5], color::BLUE);
+    assert_eq!(canvas.pixels[0], color::RED);
+}
+
+#[test]
+fn translucent_colors_blend_with_the_pixel() {
+    let half_white = Color::rgba(255, 255, 255, 128);
+    let gray = Color::rgb(100, 100, 100);
+    let over = half_white.blend(gray, BlendMode::SourceOver);
+    assert_eq!(over, Color::rgb(178, 178, 178));
+    assert_eq!(
+        Color::rgb(200, 100, 0).blend(Color::rgb(100, 200, 50), BlendMode::Additive),
+        Color::rgb(255, 255, 50)
+    );
+    assert_eq!(
+        Color::rgba(0, 255, 0, 160).blend(Color::rgb(40, 40, 120), BlendMode::Additive),
+        Color::rgb(40, 200, 120)
+    );
+    assert_eq!(
+        color::GRAY.blend(Color::rgb(200, 100, 0), BlendMode::Multiply),
+        Color::rgb(100, 50, 0)
+    );
+    assert_eq!(
+        color::GRAY.blend(Color::rgb(255, 0, 0), BlendMode::Screen),
+        Color::rgb(255, 127, 127)
+    );
+    // transparent colors leave the pixel alone whatever the mode
+    let clear = Color::rgba(255, 0, 0, 0);
+    for &mode in [
+        BlendMode::SourceOver,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+    ]
+    .iter()
+    {
+        assert_eq!(clear.blend(gray, mode), gray);
+    }
+}
+
+#[test]
+fn blend_mode_can_be_set_per_canvas_or_per_call() {
+    let mut canvas = Canvas::new(8, 8);
+    canvas.clear(Color::rgb(100, 0, 0));
+    canvas.set_blend_mode(BlendMode::Additive);
+    canvas.plot(0, 0, Color::rgb(0, 100, 0));
+    assert_eq!(canvas.pixels[0], Color::rgb(100, 100, 0));
+
+    canvas.with_blend_mode(BlendMode::Multiply, |canvas| {
+        canvas.plot(1, 0, color::BLACK)
+    });
+    assert_eq!(canvas.pixels[1], color::BLACK);
+    assert_eq!(canvas.blend_mode(), BlendMode::Additive);
+
+    canvas.plot_blended(2, 0, color::GREEN, BlendMode::SourceOver);
+    assert_eq!(canvas.pixels[2], color::GREEN);
+}
+
+#[test]
+fn translucent_filled_circles_cover_each_pixel_once() {
+    let mut canvas = Canvas::new(32, 32);
+    canvas.clear(color::BLACK);
+    canvas.filled_circle(Point2D::new(16, 16), 10, Color::rgba(255, 255, 255, 128));
+    let shades: Vec<Color> = canvas
+        .pixels
+        .iter()
+        .copied()
+        .filter(|&pixel| pixel != color::BLACK)
+        .collect();
+    assert!(shades.len() > 250);
+    assert!(shades
+        .iter()
+        .all(|&pixel| pixel == Color::rgb(128, 128, 128)));
+}
+
+// Sum of the red channel over the canvas, in whole pixels
+fn coverage(canvas: &Canvas) -> f32 {
+    canvas
+        .pixels
+        .iter()
+        .map(|pixel| pixel.r() as f32)
+        .sum::<f32>()
+        / 255.0
+}
+
+#[test]
+fn smooth_lines_share_each_step_between_two_pixels() {
+    let mut canvas = Canvas::new(32, 32);
+    canvas.set_antialiasing(true);
+    canvas.clear(color::BLACK);
+    canvas.line(2, 5, 20, 5, color::WHITE);
+    let row: Vec<Color> = canvas.pixels[5 * 32..6 * 32].to_vec();
+    assert!(row[2..=20].iter().all(|&pixel| pixel == color::WHITE));
+    assert!((coverage(&canvas) - 19.0).abs() < 0.01);
+
+    canvas.clear(color::BLACK);
+    canvas.smooth_line(FVec2D::new(2.0, 2.0), FVec2D::new(22.0, 12.0), color::WHITE);
+    // a step between two rows is shared out, not drawn twice
+    assert!((coverage(&canvas) - 21.0).abs() < 0.1);
+    assert!(canvas
+        .pixels
+        .iter()
+        .any(|&pixel| pixel.r() > 0 && pixel.r() < 255));
+}
+
+#[test]
+fn smooth_circles_cover_their_area() {
+    let mut canvas = Canvas::new(64, 64);
+    canvas.clear(color::BLACK);
+    canvas.smooth_filled_circle(FVec2D::new(31.5, 30.25), 12.0, color::WHITE);
+    let area = std::f32::consts::PI * 12.0 * 12.0;
+    assert!((coverage(&canvas) - area).abs() < area * 0.02);
+
+    canvas.clear(color::BLACK);
+    canvas.smooth_circle(FVec2D::new(32.0, 32.0), 20.0, color::WHITE);
+    let length = 2.0 * std::f32::consts::PI * 20.0;
+    assert!((coverage(&canvas) - length).abs() < length * 0.05);
+}
+
+// Pixels that were drawn on
+fn painted(canvas: &Canvas) -> usize {
+    canvas
+        .pixels
+        .iter()
+        .filter(|&&pixel| pixel != color::BLACK)
+        .count()
+}
+
+#[test]
+fn thick_line_caps_cover_the_ends() {
+    let mut canvas = Canvas::new(64, 32);
+    let (start, end) = (FVec2D::new(10.0, 16.0), FVec2D::new(40.0, 16.0));
+    canvas.thick_line(start, end, 5.0, LineCap::Butt, color::WHITE);
+    assert_eq!(painted(&canvas), 31 * 5);
+
+    canvas.clear(color::BLACK);
+    canvas.thick_line(start, end, 5.0, LineCap::Square, color::WHITE);
+    assert_eq!(painted(&canvas), 35 * 5);
+
+    canvas.clear(color::BLACK);
+    canvas.thick_line(start, end, 5.0, LineCap::Round, color::WHITE);
+    let round = painted(&canvas);
+    assert!(round > 31 * 5 && round < 35 * 5);
+}
+
+#[test]
+fn polyline_joins_fill_the_corners() {
+    let points = [
+        FVec2D::new(10.0, 40.0),
+        FVec2D::new(30.0, 10.0),
+        FVec2D::new(50.0, 40.0),
+    ];
+    let mut covered = Vec::new();
+    for &join in [LineJoin::Bevel, LineJoin::Round, LineJoin::Miter].iter() {
+        let mut canvas = Canvas::new(64, 64);
+        canvas.thick_polyline(&points, 8.0, LineCap::Butt, join, color::WHITE);
+        covered.push(painted(&canvas));
+    }
+    assert!(covered[0] < covered[1] && covered[1] < covered[2]);
+
+    // overlapping pieces are drawn once, a translucent polyline has a single shade
+    let mut canvas = Canvas::new(64, 64);
+    canvas.thick_polyline(
+        &points,
+        8.0,
+        LineCap::Round,
+        LineJoin::Round,
+        Color::rgba(255, 255, 255, 128),
+    );
+    assert!(canvas
+        .pixels
+        .iter()
+        .all(|&pixel| pixel == color::BLACK || pixel == Color::rgb(128, 128, 128)));
+}
+
+#[test]
+fn triangles_sharing_an_edge_draw_its_pixels_once() {
+    let mut canvas = Canvas::new(32, 32);
+    let half_white = Color::rgba(255, 255, 255, 128);
+    let corners = [
+        Point2D::new(4, 4),
+        Point2D::new(24, 4),
+        Point2D::new(24, 20),
+        Point2D::new(4, 20),
+    ];
+    canvas.filled_triangle(corners[0], corners[1], corners[2], half_white);
+    canvas.filled_triangle(corners[0], corners[2], corners[3], half_white);
+    // the top and left edges of the square are in, the bottom and right edges are out
+    assert_eq!(painted(&canvas), 20 * 16);
+    assert!(canvas
+        .pixels
+        .iter()
+        .all(|&pixel| pixel == color::BLACK || pixel == Color::rgb(128, 128, 128)));
+}
+
+#[test]
+fn filled_triangles_and_polygons_agree() {
+    let mut rand = StdRng::seed_from_u64(7);
+    let mut point = || Point2D::new(rand.gen_range(-5..45), rand.gen_range(-5..45));
+    for _ in 0..50 {
+        let (a, b, c) = (point(), point(), point());
+        let mut triangle = Canvas::new(40, 40);
+        triangle.filled_triangle(a, b, c, color::WHITE);
+        let mut polygon = Canvas::new(40, 40);
+        polygon.filled_polygon(&[a, b, c], FillRule::EvenOdd, color::WHITE);
+        assert!(triangle.pixels == polygon.pixels, "{:?}", (a, b, c));
+    }
+}
+
+#[test]
+fn fill_rules_differ_inside_a_star() {
+    let star = [
+        Point2D::new(20, 2),
+        Point2D::new(31, 36),
+        Point2D::new(2, 15),
+        Point2D::new(38, 15),
+        Point2D::new(9, 36),
+    ];
+    let center = 20 * 40 + 20;
+    let mut canvas = Canvas::new(40, 40);
+    canvas.filled_polygon(&star, FillRule::NonZero, color::WHITE);
+    assert_eq!(canvas.pixels[center], color::WHITE);
+    let filled = painted(&canvas);
+
+    canvas.clear(color::BLACK);
+    canvas.filled_polygon(&star, FillRule::EvenOdd, color::WHITE);
+    assert_eq!(canvas.pixels[center], color::BLACK);
+    assert!(painted(&canvas) < filled);
+}
+
+#[test]
+fn clipped_lines_keep_their_pixels() {
+    // the same lines drawn whole into a large canvas, the small canvas is a window onto it
+    let lines = [
+        ((-300, -170), (330, 200)),
+        ((7, -400), (-5, 420)),
+        ((-250, 90), (260, -60)),
+        ((300, 13), (-290, 2)),
+        ((-40, -30), (10, 50)),
+    ];
+    let offset = Point2D::new(350, 450);
+    for &antialiasing in [false, true].iter() {
+        for &((x0, y0), (x1, y1)) in lines.iter() {
+            let mut window = Canvas::new(16, 16);
+            window.set_antialiasing(antialiasing);
+            window.line(x0, y0, x1, y1, color::WHITE);
+            let mut whole = Canvas::new(700, 900);
+            whole.set_antialiasing(antialiasing);
+            whole.line_between(
+                Point2D::new(x0, y0) + offset,
+                Point2D::new(x1, y1) + offset,
+                color::WHITE,
+            );
+            for y in 0..16 {
+                for x in 0..16 {
+                    let expected = whole.pixels[((y + offset.y) * 700 + x + offset.x) as usize];
+                    assert_eq!(
+                        window.pixels[(y * 16 + x) as usize],
+                        expected,
+                        "{:?} at {}, {}",
+                        ((x0, y0), (x1, y1), antialiasing),
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn shapes_far_off_the_canvas_are_clipped() {
+    let mut canvas = Canvas::new(16, 16);
+    canvas.line(-1_000_000, 5, 1_000_000, 5, color::WHITE);
+    assert_eq!(painted(&canvas), 16);
+    assert!((0..16).all(|x| canvas.pixels[5 * 16 + x] == color::WHITE));
+
+    canvas.clear(color::BLACK);
+    canvas.line(-1_000_000, -999_990, 1_000_000, 1_000_010, color::WHITE);
+    assert_eq!(painted(&canvas), 6);
+    assert!((0..6).all(|x| canvas.pixels[(x + 10) * 16 + x] == color::WHITE));
+
+    canvas.clear(color::BLACK);
+    canvas.filled_circle(Point2D::new(-5_000_000, 8), 4_000_000, color::WHITE);
+    assert_eq!(painted(&canvas), 0);
+
+    let (a, b, c) = (
+        Point2D::new(-100_000, 3),
+        Point2D::new(50_000, -70_000),
+        Point2D::new(9, 90_000),
+    );
+    let mut triangle = Canvas::new(16, 16);
+    triangle.filled_triangle(a, b, c, color::WHITE);
+    let mut polygon = Canvas::new(16, 16);
+    polygon.filled_polygon(&[a, b, c], FillRule::NonZero, color::WHITE);
+    assert!(painted(&triangle) > 0);
+    assert!(triangle.pixels == polygon.pixels);
+
+    // corners further apart than an i32 can count
+    let mut triangle = Canvas::new(16, 16);
+    triangle.filled_triangle(
+        Point2D::new(-1_100_000_000, -10),
+        Point2D::new(1_100_000_000, -10),
+        Point2D::new(8, 1_100_000_000),
+        color::WHITE,
+    );
+    assert_eq!(painted(&triangle), 16 * 16);
+}
+
+#[test]
+fn clip_rect_confines_drawing() {
+    let mut canvas = Canvas::new(16, 16);
+    canvas.set_clip(Some(ClipRect::new(4, 4, 8, 6)));
+    canvas.filled_rectangle(Point2D::new(-5, -5), 30, 30, color::WHITE);
+    canvas.line(0, 0, 15, 15, color::RED);
+    assert_eq!(painted(&canvas), 8 * 6);
+    for (i, &pixel) in canvas.pixels.iter().enumerate() {
+        let (x, y) = ((i % 16) as i32, (i / 16) as i32);
+        assert_eq!(
+            pixel != color::BLACK,
+            ClipRect::new(4, 4, 8, 6).contains(x, y)
+        );
+    }
+
+    // the clip never reaches off the canvas, panels nest inside it and it is restored after them
+    canvas.set_clip(Some(ClipRect::new(-3, 10, 10, 20)));
+    assert_eq!(canvas.clip(), ClipRect::new(0, 10, 7, 6));
+    canvas.with_clip(ClipRect::new(5, 0, 20, 20), |canvas| {
+        assert_eq!(canvas.clip(), ClipRect::new(5, 10, 2, 6));
+        canvas.clear(color::BLACK);
+        canvas.filled_circle(Point2D::new(8, 8), 20, color::GREEN);
+    });
+    assert_eq!(canvas.clip(), ClipRect::new(0, 10, 7, 6));
+    assert_eq!(painted(&canvas), 2 * 6);
+
+    canvas.set_clip(None);
+    canvas.with_clip(ClipRect::new(20, 20, 5, 5), |canvas| {
+        assert!(canvas.clip().is_empty());
+        canvas.filled_rectangle(Point2D::new(0, 0), 15, 15, color::WHITE);
+    });
+    assert_eq!(painted(&canvas), 2 * 6);
+    assert_eq!(canvas.clip(), ClipRect::new(0, 0, 16, 16));
+}
+
+#[test]
+fn text_draws_glyphs_from_the_font() {
+    let mut canvas = Canvas::new(40, 40);
+    canvas.text(Point2D::new(2, 3), "H", color::WHITE, 1);
+    // the crossbar of the H, on the fourth row of its glyph
+    let crossbar: Vec<bool> = (2..10)
+        .map(|x| canvas.pixels[6 * 40 + x] == color::WHITE)
+        .collect();
+    assert_eq!(
+        crossbar,
+        [true, true, true, true, true, true, false, false].to_vec()
+    );
+    let lit: u32 = font::glyph('H')
+        .unwrap()
+        .iter()
+        .map(|row| row.count_ones())
+        .sum();
+    assert_eq!(painted(&canvas), lit as usize);
+
+    canvas.clear(color::BLACK);
+    canvas.text(Point2D::new(0, 0), "H\u{e9}\nH", color::WHITE, 2);
+    assert_eq!(painted(&canvas), lit as usize * 2 * 4);
+    assert_eq!(font::measure("H\u{e9}\nH", 2), Point2D::new(32, 32));
+}
+
+// 2x2 sprite: red, green on top, blue and see through below
+fn checker() -> Sprite {
+    let clear = Color::rgba(0, 0, 0, 0);
+    Sprite::from_pixels(2, 2, vec![color::RED, color::GREEN, color::BLUE, clear]).unwrap()
+}
+
+#[test]
+fn sprites_are_drawn_with_their_alpha() {
+    let mut canvas = Canvas::new(8, 8);
+    canvas.clear(color::WHITE);
+    canvas.draw_sprite(&checker(), Point2D::new(3, 2));
+    assert_eq!(canvas.pixels[2 * 8 + 3], color::RED);
+    assert_eq!(canvas.pixels[2 * 8 + 4], color::GREEN);
+    assert_eq!(canvas.pixels[3 * 8 + 3], color::BLUE);
+    assert_eq!(canvas.pixels[3 * 8 + 4], color::WHITE);
+    assert_eq!(painted(&canvas), 64);
+    assert!(Sprite::from_pixels(3, 2, vec![color::RED; 4]).is_none());
+    // 65536 * 65536 wraps to 0 in u32
+    assert!(Sprite::from_pixels(65536, 65536, Vec::new()).is_none());
+}
+
+#[test]
+fn sprites_scale_rotate_and_come_from_sheets() {
+    let mut canvas = Canvas::new(8, 8);
+    canvas.draw_sprite_scaled(&checker(), Point2D::new(0, 0), 4, 4);
+    assert_eq!(canvas.pixels[8 + 1], color::RED);
+    assert_eq!(canvas.pixels[3 * 8], color::BLUE);
+    assert_eq!(canvas.pixels[2], color::GREEN);
+    assert_eq!(painted(&canvas), 12);
+
+    // a quarter turn clockwise takes the top left pixel to the top right
+    canvas.clear(color::BLACK);
+    canvas.draw_sprite_rotated(
+        &checker(),
+        FVec2D::new(4.0, 4.0),
+        std::f32::consts::FRAC_PI_2,
+        1.0,
+    );
+    assert_eq!(canvas.pixels[3 * 8 + 4], color::RED);
+    assert_eq!(canvas.pixels[4 * 8 + 4], color::GREEN);
+    assert_eq!(canvas.pixels[3 * 8 + 3], color::BLUE);
+    assert_eq!(painted(&canvas), 3);
+
+    // a sheet of two frames side by side
+    let mut sheet = Sprite::new(4, 2);
+    for y in 0..2 {
+        for x in 0..4 {
+            sheet.set(x, y, if x < 2 { color::RED } else { color::CYAN });
+        }
+    }
+    let frame = SpriteRect::new(2, 0, 2, 2);
+    canvas.clear(color::BLACK);
+    canvas.draw_sprite_region(&sheet, frame, Point2D::new(5, 5), 2, 2);
+    assert_eq!(painted(&canvas), 4);
+    assert_eq!(canvas.pixels[6 * 8 + 6], color::CYAN);
+    assert_eq!(sheet.region(frame).pixels, vec![color::CYAN; 4]);
+}
+
+#[test]
+fn ppm_and_pam_images_round_trip() {
+    let mut canvas = Canvas::new(5, 3);
+    canvas.clear(color::MAGENTA);
+    canvas.plot(1, 2, Color::rgb(1, 2, 3));
+    canvas.plot(4, 0, Color::rgba(200, 100, 50, 25));
+    let (width, height) = (canvas.width(), canvas.height());
+    let opaque: Vec<Color> = canvas
+        .pixels
+        .iter()
+        .map(|pixel| Color::rgb(pixel.r(), pixel.g(), pixel.b()))
+        .collect();
+
+    for &format in [PpmFormat::Binary, PpmFormat::Plain].iter() {
+        let bytes = netpbm::encode_ppm(width, height, &canvas.pixels, format);
+        assert_eq!(netpbm::decode_ppm(&bytes).unwrap(), (5, 3, opaque.clone()));
+    }
+    let bytes = netpbm::encode_pam(width, height, &canvas.pixels);
+    assert_eq!(
+        netpbm::decode_pam(&bytes).unwrap(),
+        (5, 3, canvas.pixels.clone())
+    );
+
+    let path = std::env::temp_dir().join("circle_collision_round_trip.pam");
+    canvas.save_pam(&path).unwrap();
+    let loaded = Canvas::load_pam(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!((loaded.width(), loaded.height()), (5, 3));
+    assert!(loaded.pixels == canvas.pixels);
+}
+
+#[test]
+fn ppm_reader_handles_comments_and_other_sample_sizes() {
+    let plain = b"P3 # comment\n2 1 # size\n15\n15 0 0  0 15 15\n";
+    assert_eq!(
+        netpbm::decode_ppm(plain).unwrap(),
+        (2, 1, vec![color::RED, color::CYAN])
+    );
+    let mut wide = b"P6 1 1 65535\n".to_vec();
+    wide.extend_from_slice(&[0xFF, 0xFF, 0x80, 0x00, 0x00, 0x00]);
+    assert_eq!(
+        netpbm::decode_ppm(&wide).unwrap(),
+        (1, 1, vec![Color::rgb(255, 128, 0)])
+    );
+    let gray =
+        b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x40\x80";
+    assert_eq!(
+        netpbm::decode_pam(gray).unwrap(),
+        (1, 1, vec![Color::rgba(64, 64, 64, 128)])
+    );
+
+    assert!(netpbm::decode_ppm(b"P6 2 2 255\n\x00\x00\x00").is_err());
+    assert!(netpbm::decode_ppm(b"P5 1 1 255\n\x00").is_err());
+    assert!(netpbm::decode_pam(b"P7\nWIDTH 1\nENDHDR\n").is_err());
+
+    // sizes whose sample count does not fit in memory are refused before reading any
+    let huge = netpbm::decode_ppm(b"P6 4000000000 4000000000 255\n").unwrap_err();
+    assert_eq!(huge.kind(), ErrorKind::InvalidData);
+    let huge = b"P7\nWIDTH 4000000000\nHEIGHT 4000000000\nDEPTH 4\nMAXVAL 255\nENDHDR\n";
+    assert_eq!(
+        netpbm::decode_pam(huge).unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn png_images_round_trip() {
+    let mut canvas = Canvas::new(37, 21);
+    canvas.clear(Color::rgba(10, 20, 30, 40));
+    canvas.filled_circle(Point2D::new(18, 10), 8, color::YELLOW);
+    canvas.text(Point2D::new(1, 1), "Hi", Color::rgba(255, 0, 255, 200), 1);
+    for &compression in [PngCompression::Deflate, PngCompression::Stored].iter() {
+        let bytes = png::encode_png(37, 21, &canvas.pixels, compression);
+        assert_eq!(
+            png::decode_png(&bytes).unwrap(),
+            (37, 21, canvas.pixels.clone())
+        );
+    }
+
+    let path = std::env::temp_dir().join("circle_collision_round_trip.png");
+    canvas.save_png(&path, PngCompression::Deflate).unwrap();
+    let loaded = Canvas::load_png(&path).unwrap();
+    let sprite = Sprite::load_png(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(loaded.pixels == canvas.pixels);
+    assert_eq!((sprite.width(), sprite.height()), (37, 21));
+
+    // deflate finds the repeats of a flat image
+    let flat = Canvas::new(200, 200);
+    let bytes = png::encode_png(200, 200, &flat.pixels, PngCompression::Deflate);
+    assert!(bytes.len() < 2000);
+}
+
+#[test]
+fn png_reader_reads_images_from_other_encoders() {
+    // 3x2 RGB image compressed by zlib, rows filtered with Sub and Paeth, with a text chunk
+    let image: [u8; 113] = [
+        137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 2, 8, 2,
+        0, 0, 0, 18, 22, 241, 77, 0, 0, 0, 20, 116, 69, 88, 116, 67, 111, 109, 109, 101, 110, 116,
+        0, 109, 97, 100, 101, 32, 98, 121, 32, 122, 108, 105, 98, 120, 66, 197, 156, 0, 0, 0, 24,
+        73, 68, 65, 84, 120, 218, 99, 252, 207, 192, 192, 8, 198, 44, 220, 34, 114, 26, 198, 114,
+        114, 1, 209, 0, 50, 208, 4, 132, 137, 94, 78, 161, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96,
+        130,
+    ];
+    let expected = vec![
+        color::RED,
+        color::GREEN,
+        color::BLUE,
+        Color::rgb(10, 20, 30),
+        Color::rgb(40, 50, 60),
+        Color::rgb(70, 80, 90),
+    ];
+    assert_eq!(png::decode_png(&image).unwrap(), (3, 2, expected));
+
+    let mut corrupt = image;
+    corrupt[90] ^= 1;
+    assert!(png::decode_png(&corrupt).is_err());
+    assert!(png::decode_png(&image[..60]).is_err());
+}
+
+#[test]
+fn zlib_streams_with_dynamic_codes_inflate() {
+    let text: String = (0..40)
+        .map(|i| format!("circle {} collides with circle {}; ", i % 7, i % 5))
+        .collect();
+    let stream: [u8; 138] = [
+        120, 218, 205, 212, 59, 10, 66, 49, 16, 70, 225, 173, 100, 9, 243, 110, 238, 114, 226, 5,
+        3, 1, 65, 133, 187, 125, 27, 77, 21, 56, 173, 221, 240, 115, 138, 175, 154, 62, 158, 125,
+        158, 77, 90, 127, 204, 57, 110, 231, 171, 93, 227, 125, 111, 253, 59, 31, 191, 75, 247,
+        129, 174, 192, 246, 129, 173, 192, 247, 129, 175, 32, 246, 65, 172, 32, 9, 89, 132, 20, 66,
+        42, 33, 141, 144, 78, 200, 32, 100, 18, 178, 8, 41, 132, 84, 66, 26, 33, 157, 144, 65, 200,
+        36, 100, 17, 82, 8, 169, 132, 52, 66, 58, 33, 131, 144, 73, 200, 34, 164, 16, 82, 9, 105,
+        132, 116, 66, 6, 33, 147, 144, 69, 200, 255, 126, 65, 31, 152, 50, 196, 99,
+    ];
+    assert_eq!(deflate::decompress(&stream).unwrap(), text.as_bytes());
+
+    let compressed = deflate::compress(text.as_bytes(), false);
+    assert!(compressed.len() < text.len() / 4);
+    assert_eq!(deflate::decompress(&compressed).unwrap(), text.as_bytes());
+    assert_eq!(
+        deflate::decompress(&deflate::compress(&[], true)).unwrap(),
+        []
+    );
+    assert_eq!(png::crc32(b"IEND"), 0xAE42_6082);
+}
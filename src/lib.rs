@@ -1,3 +1,4 @@
 pub mod balls;
 pub mod grfx;
 pub mod math;
+pub mod physics;
@@ -0,0 +1,399 @@
+use crate::balls::Circle;
+use crate::grfx::color::Color;
+use crate::math::vector::FVec2D;
+use crate::physics::integrator::Integrator;
+use crate::physics::parallel;
+use crate::physics::query::Aabb;
+use std::iter::FromIterator;
+
+///
+/// Every circle in the world stored as a structure of arrays: one array per property,
+/// all indexed by the same body index.
+///
+/// The stepping loops only touch a few properties of each body, keeping each property packed
+/// together means they read far less memory than walking a list of whole circles.
+///
+/// User code reads bodies as Circle values with get or iter and changes them
+/// through the view returned by circle_mut, or by replacing them with set.
+///
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Bodies {
+    pub(crate) positions: Vec<FVec2D>,
+    pub(crate) velocities: Vec<FVec2D>,
+    pub(crate) radii: Vec<f32>,
+    /// zero for bodies that can not be moved by collisions
+    pub(crate) inverse_masses: Vec<f32>,
+    pub(crate) prev_positions: Vec<FVec2D>,
+    /// spins are only changed by contacts in the XPBD solver and in granular mode
+    pub(crate) angles: Vec<f32>,
+    pub(crate) angular_speeds: Vec<f32>,
+    pub(crate) prev_angles: Vec<f32>,
+    pub(crate) accelerations: Vec<FVec2D>,
+    pub(crate) time_remaining: Vec<f32>,
+    pub(crate) colors: Vec<Color>,
+    pub(crate) selected: Vec<bool>,
+    /// seconds left before the world despawns the body, infinite for bodies that stay
+    pub(crate) lifetimes: Vec<f32>,
+}
+
+///
+/// Mutable view of one body, shaped like a Circle.
+/// Writes go straight to the arrays holding the body.
+///
+pub struct CircleMut<'a> {
+    pub center: &'a mut FVec2D,
+    pub radius: &'a mut f32,
+    pub speed: &'a mut FVec2D,
+    pub angle: &'a mut f32,
+    pub angular_speed: &'a mut f32,
+    pub inverse_mass: &'a mut f32,
+    pub color: &'a mut Color,
+    pub selected: &'a mut bool,
+}
+
+impl CircleMut<'_> {
+    pub fn mass(&self) -> f32 {
+        1.0 / *self.inverse_mass
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        *self.inverse_mass = 1.0 / mass;
+    }
+}
+
+impl Bodies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Add a body and return its index
+    pub fn push(&mut self, circle: Circle) -> usize {
+        self.positions.push(circle.center);
+        self.velocities.push(circle.speed);
+        self.radii.push(circle.radius);
+        self.inverse_masses.push(1.0 / circle.mass);
+        self.prev_positions.push(circle.prev_center);
+        self.angles.push(circle.angle);
+        self.angular_speeds.push(circle.angular_speed);
+        self.prev_angles.push(circle.angle);
+        self.accelerations.push(circle.acceletation);
+        self.time_remaining.push(circle.sim_time_remaining);
+        self.colors.push(circle.color);
+        self.selected.push(circle.selected);
+        self.lifetimes.push(f32::INFINITY);
+        self.len() - 1
+    }
+
+    /// Remove body i and return it, the last body takes its index
+    pub fn swap_remove(&mut self, i: usize) -> Circle {
+        let circle = self.get(i);
+        self.positions.swap_remove(i);
+        self.velocities.swap_remove(i);
+        self.radii.swap_remove(i);
+        self.inverse_masses.swap_remove(i);
+        self.prev_positions.swap_remove(i);
+        self.angles.swap_remove(i);
+        self.angular_speeds.swap_remove(i);
+        self.prev_angles.swap_remove(i);
+        self.accelerations.swap_remove(i);
+        self.time_remaining.swap_remove(i);
+        self.colors.swap_remove(i);
+        self.selected.swap_remove(i);
+        self.lifetimes.swap_remove(i);
+        circle
+    }
+
+    /// Copy of body i as a Circle
+    pub fn get(&self, i: usize) -> Circle {
+        Circle {
+            center: self.positions[i],
+            radius: self.radii[i],
+            speed: self.velocities[i],
+            angle: self.angles[i],
+            angular_speed: self.angular_speeds[i],
+            sim_time_remaining: self.time_remaining[i],
+            prev_center: self.prev_positions[i],
+            mass: 1.0 / self.inverse_masses[i],
+            acceletation: self.accelerations[i],
+            color: self.colors[i],
+            selected: self.selected[i],
+        }
+    }
+
+    // Only the parts of body i needed to solve a contact, reading fewer arrays than get
+    pub(crate) fn contact_circle(&self, i: usize) -> Circle {
+        let mut circle = Circle::new(
+            self.positions[i],
+            self.radii[i],
+            self.velocities[i],
+            Color::default(),
+        );
+        circle.mass = 1.0 / self.inverse_masses[i];
+        circle.angular_speed = self.angular_speeds[i];
+        circle
+    }
+
+    /// Replace body i with the given circle
+    pub fn set(&mut self, i: usize, circle: Circle) {
+        self.positions[i] = circle.center;
+        self.velocities[i] = circle.speed;
+        self.radii[i] = circle.radius;
+        self.inverse_masses[i] = 1.0 / circle.mass;
+        self.prev_positions[i] = circle.prev_center;
+        self.angles[i] = circle.angle;
+        self.angular_speeds[i] = circle.angular_speed;
+        self.prev_angles[i] = circle.angle;
+        self.accelerations[i] = circle.acceletation;
+        self.time_remaining[i] = circle.sim_time_remaining;
+        self.colors[i] = circle.color;
+        self.selected[i] = circle.selected;
+    }
+
+    /// Mutable view of body i
+    pub fn circle_mut(&mut self, i: usize) -> CircleMut<'_> {
+        CircleMut {
+            center: &mut self.positions[i],
+            radius: &mut self.radii[i],
+            speed: &mut self.velocities[i],
+            angle: &mut self.angles[i],
+            angular_speed: &mut self.angular_speeds[i],
+            inverse_mass: &mut self.inverse_masses[i],
+            color: &mut self.colors[i],
+            selected: &mut self.selected[i],
+        }
+    }
+
+    /// Copies of every body as circles, in index order
+    pub fn iter(&self) -> impl Iterator<Item = Circle> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<Circle> {
+        self.iter().collect()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn positions(&self) -> &[FVec2D] {
+        &self.positions
+    }
+
+    pub fn velocities(&self) -> &[FVec2D] {
+        &self.velocities
+    }
+
+    pub fn radii(&self) -> &[f32] {
+        &self.radii
+    }
+
+    pub fn inverse_masses(&self) -> &[f32] {
+        &self.inverse_masses
+    }
+
+    pub fn angles(&self) -> &[f32] {
+        &self.angles
+    }
+
+    pub fn angular_speeds(&self) -> &[f32] {
+        &self.angular_speeds
+    }
+
+    /// Inverse moment of inertia of body i, a solid disc of its mass and radius
+    pub fn inverse_inertia(&self, i: usize) -> f32 {
+        2.0 * self.inverse_masses[i] / (self.radii[i] * self.radii[i])
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// Seconds each body has left before the world despawns it, infinite for bodies that stay
+    pub fn lifetimes(&self) -> &[f32] {
+        &self.lifetimes
+    }
+
+    pub fn set_lifetime(&mut self, i: usize, seconds: f32) {
+        self.lifetimes[i] = seconds;
+    }
+
+    ///
+    /// Move every body with time left by its speed after applying gravity and drag.
+    /// With wrap set to a size, bodies leaving the area between (0, 0) and size wrap around
+    /// to the other side.
+    ///
+    pub fn integrate(
+        &mut self,
+        integrator: Integrator,
+        gravity: FVec2D,
+        drag: f32,
+        wrap: Option<FVec2D>,
+        threads: usize,
+    ) {
+        let chunk = parallel::chunk_size(self.len(), threads);
+        let chunks = self
+            .positions
+            .chunks_mut(chunk)
+            .zip(self.velocities.chunks_mut(chunk))
+            .zip(self.prev_positions.chunks_mut(chunk))
+            .zip(self.accelerations.chunks_mut(chunk))
+            .zip(self.time_remaining.chunks_mut(chunk));
+        parallel::run(
+            chunks,
+            |((((positions, velocities), prev_positions), accelerations), time_remaining)| {
+                let bodies = positions
+                    .iter_mut()
+                    .zip(velocities.iter_mut())
+                    .zip(prev_positions.iter_mut())
+                    .zip(accelerations.iter_mut())
+                    .zip(time_remaining.iter());
+                for ((((position, velocity), prev_position), acceleration), &time) in bodies {
+                    if time > 0.0 {
+                        // cache current center
+                        *prev_position = *position;
+
+                        // drag force + gravity
+                        *acceleration = integrator
+                            .advance(position, velocity, time, |_, v| -v * drag + gravity);
+                        if let Some(size) = wrap {
+                            if position.x < 0.0 {
+                                position.x += size.x;
+                            }
+                            if position.y < 0.0 {
+                                position.y += size.y;
+                            }
+                            if position.x > size.x {
+                                position.x -= size.x;
+                            }
+                            if position.y > size.y {
+                                position.y -= size.y;
+                            }
+                        }
+                        if velocity.squared_length() <= 0.01 * 0.01 {
+                            *velocity = FVec2D::new(0.0, 0.0);
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    /// Bounding box of every body grown by how far it can travel in delta_t
+    pub fn bounds(&self, delta_t: f32) -> Vec<Aabb> {
+        self.positions
+            .iter()
+            .zip(self.radii.iter())
+            .zip(self.velocities.iter())
+            .map(|((&center, &radius), speed)| {
+                let extent = radius + speed.length() * delta_t;
+                let extent = FVec2D::new(extent, extent);
+                Aabb {
+                    min: center - extent,
+                    max: center + extent,
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<Circle>> for Bodies {
+    fn from(circles: Vec<Circle>) -> Self {
+        circles.into_iter().collect()
+    }
+}
+
+impl FromIterator<Circle> for Bodies {
+    fn from_iter<I: IntoIterator<Item = Circle>>(circles: I) -> Self {
+        let mut bodies = Self::new();
+        for circle in circles {
+            bodies.push(circle);
+        }
+        bodies
+    }
+}
+
+///
+/// Read access to a collection of circles.
+/// Lets queries and measurements work on plain lists of circles as well as on Bodies.
+///
+pub trait Circles {
+    /// Number of circles
+    fn count(&self) -> usize;
+    /// Copy of circle i
+    fn circle(&self, i: usize) -> Circle;
+
+    // Single properties of circle i, for loops that need only a few of them.
+    // Bodies reads them straight from their arrays instead of copying whole circles.
+
+    fn center(&self, i: usize) -> FVec2D {
+        self.circle(i).center
+    }
+
+    fn radius(&self, i: usize) -> f32 {
+        self.circle(i).radius
+    }
+
+    fn speed(&self, i: usize) -> FVec2D {
+        self.circle(i).speed
+    }
+
+    /// Zero for circles that can not be moved by collisions
+    fn inverse_mass(&self, i: usize) -> f32 {
+        1.0 / self.circle(i).mass
+    }
+}
+
+impl Circles for [Circle] {
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn circle(&self, i: usize) -> Circle {
+        self[i]
+    }
+}
+
+impl Circles for Vec<Circle> {
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn circle(&self, i: usize) -> Circle {
+        self[i]
+    }
+}
+
+impl Circles for Bodies {
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    fn circle(&self, i: usize) -> Circle {
+        self.get(i)
+    }
+
+    fn center(&self, i: usize) -> FVec2D {
+        self.positions[i]
+    }
+
+    fn radius(&self, i: usize) -> f32 {
+        self.radii[i]
+    }
+
+    fn speed(&self, i: usize) -> FVec2D {
+        self.velocities[i]
+    }
+
+    fn inverse_mass(&self, i: usize) -> f32 {
+        self.inverse_masses[i]
+    }
+}
@@ -0,0 +1,133 @@
+use crate::math::vector::FVec2D;
+use crate::physics::query::Aabb;
+
+///
+/// Uniform grid used to find bodies that may touch without testing every pair.
+/// Each body is stored in every cell its bounding box overlaps.
+///
+/// A pair sharing several cells is only reported from the cell holding the top left corner
+/// of the overlap of their boxes, so every pair is found exactly once.
+///
+pub struct Grid {
+    cell_size: f32,
+    // cell coordinates of the top left cell
+    origin: (i32, i32),
+    columns: i32,
+    rows: i32,
+    // bodies in cell c are entries[starts[c]..starts[c + 1]]
+    starts: Vec<usize>,
+    entries: Vec<usize>,
+}
+
+impl Grid {
+    ///
+    /// Build a grid over the given bounding boxes
+    ///     bounds -> bounding box of each body, bodies are refered to by their index
+    ///     cell_size -> width and height of each cell, around the size of a typical body works best
+    ///
+    /// Cells grow when the boxes are spread too far apart for the grid to fit in memory.
+    ///
+    pub fn new(bounds: &[Aabb], cell_size: f32) -> Self {
+        let mut grid = Self {
+            cell_size: cell_size.max(1.0),
+            origin: (0, 0),
+            columns: 1,
+            rows: 1,
+            starts: Vec::new(),
+            entries: Vec::new(),
+        };
+        if let Some(first) = bounds.first() {
+            let mut area = *first;
+            for aabb in bounds.iter() {
+                area = Aabb::new(
+                    FVec2D::new(area.min.x.min(aabb.min.x), area.min.y.min(aabb.min.y)),
+                    FVec2D::new(area.max.x.max(aabb.max.x), area.max.y.max(aabb.max.y)),
+                );
+            }
+            let max_cells = (bounds.len() * 4).max(1024) as f32;
+            let size = area.max - area.min;
+            while (size.x / grid.cell_size + 2.0) * (size.y / grid.cell_size + 2.0) > max_cells {
+                grid.cell_size *= 2.0;
+            }
+            let min = grid.raw_cell(area.min);
+            let max = grid.raw_cell(area.max);
+            grid.origin = min;
+            grid.columns = max.0 - min.0 + 1;
+            grid.rows = max.1 - min.1 + 1;
+        }
+
+        // count the bodies in each cell, then place them
+        let cells = (grid.columns * grid.rows) as usize;
+        let mut counts = vec![0; cells + 1];
+        for aabb in bounds.iter() {
+            grid.for_each_cell(aabb, |cell| counts[cell] += 1);
+        }
+        let mut start = 0;
+        for count in counts.iter_mut() {
+            let cell_count = *count;
+            *count = start;
+            start += cell_count;
+        }
+        let mut next = counts.clone();
+        let mut entries = vec![0; start];
+        for (i, aabb) in bounds.iter().enumerate() {
+            grid.for_each_cell(aabb, |cell| {
+                entries[next[cell]] = i;
+                next[cell] += 1;
+            });
+        }
+        grid.starts = counts;
+        grid.entries = entries;
+        grid
+    }
+
+    // Cell coordinates containing the given point
+    fn raw_cell(&self, point: FVec2D) -> (i32, i32) {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    // Index of the cell containing the given point
+    fn cell(&self, point: FVec2D) -> usize {
+        let (x, y) = self.raw_cell(point);
+        let x = (x - self.origin.0).max(0).min(self.columns - 1);
+        let y = (y - self.origin.1).max(0).min(self.rows - 1);
+        (y * self.columns + x) as usize
+    }
+
+    // Call found with the index of every cell the box overlaps
+    fn for_each_cell<F: FnMut(usize)>(&self, aabb: &Aabb, mut found: F) {
+        let (min, max) = (self.cell(aabb.min), self.cell(aabb.max));
+        let columns = self.columns as usize;
+        for row in (min / columns)..=(max / columns) {
+            for column in (min % columns)..=(max % columns) {
+                found(row * columns + column);
+            }
+        }
+    }
+
+    ///
+    /// Find every body with a higher index than i whose bounding box overlaps the box of body i.
+    /// Bodies are reported in a fixed order for a given set of boxes.
+    ///
+    pub fn candidates<F: FnMut(usize)>(&self, i: usize, bounds: &[Aabb], mut found: F) {
+        let aabb = &bounds[i];
+        self.for_each_cell(aabb, |cell| {
+            for &j in self.entries[self.starts[cell]..self.starts[cell + 1]].iter() {
+                if j <= i {
+                    continue;
+                }
+                let other = &bounds[j];
+                if !aabb.overlaps(other) {
+                    continue;
+                }
+                let corner = FVec2D::new(aabb.min.x.max(other.min.x), aabb.min.y.max(other.min.y));
+                if self.cell(corner) == cell {
+                    found(j);
+                }
+            }
+        });
+    }
+}
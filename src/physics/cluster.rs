@@ -0,0 +1,53 @@
+use crate::physics::bodies::Bodies;
+use crate::physics::xpbd::DistanceConstraint;
+
+///
+/// Circles welded together into one body that shatters back into its circles when hit hard enough.
+///
+/// Neighbouring members are held together by rigid welds, only enforced by the XPBD solver.
+/// Members do not collide with each other while the cluster holds.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    /// Index of every member circle in the world
+    pub members: Vec<usize>,
+    /// Links holding the members in place
+    pub welds: Vec<DistanceConstraint>,
+    /// Impulse a single impact has to deliver to one member to shatter the cluster
+    pub shatter_impulse: f32,
+}
+
+impl Cluster {
+    ///
+    /// Weld every pair of members that are closer than the radius of the smaller one,
+    /// enough to brace circles packed in rows and columns as well as tightly packed circles.
+    ///
+    pub fn weld(circles: &Bodies, members: Vec<usize>, shatter_impulse: f32) -> Self {
+        let mut welds = Vec::new();
+        for (k, &i) in members.iter().enumerate() {
+            for &j in members.iter().skip(k + 1) {
+                let distance = (circles.positions[i] - circles.positions[j]).length();
+                let (r1, r2) = (circles.radii[i], circles.radii[j]);
+                if distance - r1 - r2 <= r1.min(r2) {
+                    welds.push(DistanceConstraint::new(i, j, distance, 0.0));
+                }
+            }
+        }
+        Self {
+            members,
+            welds,
+            shatter_impulse,
+        }
+    }
+}
+
+/// Cluster each circle belongs to, None for circles that are not part of any cluster
+pub(crate) fn membership(clusters: &[Cluster], circles: usize) -> Vec<Option<usize>> {
+    let mut membership = vec![None; circles];
+    for (c, cluster) in clusters.iter().enumerate() {
+        for &i in cluster.members.iter() {
+            membership[i] = Some(c);
+        }
+    }
+    membership
+}
@@ -0,0 +1,210 @@
+use crate::balls::Circle;
+use crate::math::vector::FVec2D;
+
+///
+/// Push two overlapping circles apart along the line joining their centers.
+/// Each circle moves half of the overlap so they end up overlapping by exactly slop.
+/// Leaving a little overlap keeps resting circles in contact instead of pushing them
+/// apart and letting them fall back every step. Pass a slop of 0 to leave them just touching.
+/// Circles sharing the same center are pushed apart horizontally.
+///
+pub fn separate_circles(first: &mut Circle, second: &mut Circle, slop: f32) {
+    let distance_vec = first.center - second.center;
+    let distance = distance_vec.length();
+    let overlap = 0.5 * (distance - first.radius - second.radius + slop);
+    if overlap >= 0.0 {
+        return;
+    }
+    let direction = if distance > 0.0 {
+        distance_vec / distance
+    } else {
+        FVec2D::new(1.0, 0.0)
+    };
+    first.center -= direction * overlap;
+    second.center += direction * overlap;
+}
+
+/// How a contact changes the speeds of the two bodies involved
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Response {
+    /// Speeds are left as they are
+    None,
+    /// The bodies stop moving towards each other, new speeds of (first, second)
+    Resting(FVec2D, FVec2D),
+    /// The bodies bounce off each other, new speeds of (first, second)
+    Bounce(FVec2D, FVec2D),
+}
+
+///
+/// Speeds of two circles in contact or about to be.
+///     delta_t -> time until the next collision check
+///     resting_speed -> approach speeds below this do not bounce
+///
+/// Fast contacts bounce elastically once the circles touch.
+/// Slow contacts are treated as resting: the circles stop moving towards each other
+/// and, when they are still apart (speculative contact), they are only slowed down enough
+/// to close the gap by the next step. This keeps stacks still without adding energy.
+///
+pub fn contact_response(
+    first: &Circle,
+    second: &Circle,
+    delta_t: f32,
+    resting_speed: f32,
+) -> Response {
+    let distance = second.center - first.center;
+    if distance.squared_length() == 0.0 {
+        return Response::None;
+    }
+    let normal = distance.unit_vector();
+    let gap = distance.length() - first.radius - second.radius;
+    let approach = FVec2D::dot(first.speed - second.speed, normal);
+    if approach <= 0.0 {
+        // already moving apart
+        return Response::None;
+    }
+    if approach < resting_speed {
+        let allowed = gap.max(0.0) / delta_t;
+        if approach <= allowed {
+            return Response::None;
+        }
+        let impulse = (approach - allowed) / (1.0 / first.mass + 1.0 / second.mass);
+        return Response::Resting(
+            first.speed - normal * (impulse / first.mass),
+            second.speed + normal * (impulse / second.mass),
+        );
+    }
+    if gap > 0.0 {
+        return Response::None;
+    }
+    let (speed1, speed2) = elastic_response(first, second);
+    Response::Bounce(speed1, speed2)
+}
+
+///
+/// Speed of a circle in contact with an edge, or about to be.
+/// The edge is represented by a fake circle sitting on the closest point of the edge.
+/// Works like contact_response except the edge never moves, so resting contacts
+/// only slow down the circle.
+///
+/// The second speed of the response is always zero
+///
+pub fn edge_response(circle: &Circle, edge: &Circle, delta_t: f32, resting_speed: f32) -> Response {
+    let distance = edge.center - circle.center;
+    if distance.squared_length() == 0.0 {
+        return Response::None;
+    }
+    let normal = distance.unit_vector();
+    let gap = distance.length() - circle.radius - edge.radius;
+    let approach = FVec2D::dot(circle.speed, normal);
+    if approach <= 0.0 {
+        return Response::None;
+    }
+    if approach < resting_speed {
+        let allowed = gap.max(0.0) / delta_t;
+        if approach <= allowed {
+            return Response::None;
+        }
+        return Response::Resting(
+            circle.speed - normal * (approach - allowed),
+            FVec2D::new(0.0, 0.0),
+        );
+    }
+    if gap > 0.0 {
+        return Response::None;
+    }
+    Response::Bounce(elastic_response(circle, edge).0, FVec2D::new(0.0, 0.0))
+}
+
+///
+/// Speeds of two colliding circles after a perfectly elastic collision.
+/// The speed along the line joining the centers is exchanged like in a 1D collision
+/// while the tangential speed of each circle is kept as is.
+///
+/// Returns the new speeds of (first, second)
+///
+pub fn elastic_response(first: &Circle, second: &Circle) -> (FVec2D, FVec2D) {
+    let distance = second.center - first.center;
+    if distance.squared_length() == 0.0 {
+        return (first.speed, second.speed);
+    }
+    let normal = distance.unit_vector();
+    let tangental = normal.perpendicular();
+
+    let tan_speed1 = FVec2D::dot(first.speed, tangental);
+    let tan_speed2 = FVec2D::dot(second.speed, tangental);
+
+    let norm_speed1 = FVec2D::dot(first.speed, normal);
+    let norm_speed2 = FVec2D::dot(second.speed, normal);
+
+    // conservation of momentum in 1D
+    // elastic collisions https://en.wikipedia.org/wiki/Elastic_collision
+    //https://www.youtube.com/watch?v=LPzyNOHY3A4&t=1077s&ab_channel=javidx9
+    let m1 = ((norm_speed1 * (first.mass - second.mass)) + 2.0 * second.mass * norm_speed2)
+        / (first.mass + second.mass);
+    let m2 = ((norm_speed2 * (second.mass - first.mass)) + 2.0 * first.mass * norm_speed1)
+        / (first.mass + second.mass);
+
+    (
+        tangental * tan_speed1 + normal * m1,
+        tangental * tan_speed2 + normal * m2,
+    )
+}
+
+/// Two bodies found touching, or about to, by the narrowphase
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Contact {
+    /// Two circles by index
+    Circles(usize, usize),
+    /// A circle by index and the fake circle standing in for the edge it touches
+    Edge(usize, Circle),
+}
+
+impl Contact {
+    /// Indices of the circles involved
+    pub fn circles(&self) -> (usize, Option<usize>) {
+        match *self {
+            Contact::Circles(first, second) => (first, Some(second)),
+            Contact::Edge(first, _) => (first, None),
+        }
+    }
+}
+
+/// Contacts split into batches by batch_contacts, each one a list of contact indices
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Batches {
+    /// No circle shows up twice in a batch, so a batch can be solved all at once
+    pub independent: Vec<Vec<usize>>,
+    /// Contacts left over once every circle ran out of batches, solved one after the other
+    pub sequential: Vec<usize>,
+}
+
+///
+/// Split contacts into batches where no circle shows up twice (greedy graph coloring).
+/// Contacts in a batch can be solved in any order, or at the same time, with the same result.
+///
+/// The batching only depends on the contacts and their order, never on the number of threads,
+/// which keeps the simulation deterministic.
+///
+pub fn batch_contacts(contacts: &[Contact], circles: usize) -> Batches {
+    // bit n is set when the circle is already in batch n
+    let mut used = vec![0u64; circles];
+    let mut batches = Batches::default();
+    for (k, contact) in contacts.iter().enumerate() {
+        let (first, second) = contact.circles();
+        let mask = used[first] | second.map_or(0, |second| used[second]);
+        let batch = (!mask).trailing_zeros() as usize;
+        if batch >= 64 {
+            batches.sequential.push(k);
+            continue;
+        }
+        used[first] |= 1 << batch;
+        if let Some(second) = second {
+            used[second] |= 1 << batch;
+        }
+        if batches.independent.len() <= batch {
+            batches.independent.resize(batch + 1, Vec::new());
+        }
+        batches.independent[batch].push(k);
+    }
+    batches
+}
@@ -0,0 +1,239 @@
+use crate::grfx::canvas::Canvas;
+use crate::grfx::clip::ClipRect;
+use crate::grfx::color;
+use crate::grfx::color::Color;
+use crate::math::vector::{FVec2D, Point2D};
+use crate::physics::bodies::Circles;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Energy and momentum of all the circles in the world at a given time
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Diagnostics {
+    pub time: f32,
+    pub kinetic_energy: f32,
+    pub potential_energy: f32,
+    pub linear_momentum: FVec2D,
+    pub angular_momentum: f32,
+}
+
+impl Diagnostics {
+    ///
+    /// Measure the energy and momentum of the given circles, static circles are left out
+    ///     time -> simulation time the measurement belongs to
+    ///     gravity -> acceleration used for the potential energy
+    ///     ground -> point where the potential energy is zero
+    ///     pivot -> point the angular momentum is measured around
+    ///
+    pub fn measure<C: Circles + ?Sized>(
+        time: f32,
+        circles: &C,
+        gravity: FVec2D,
+        ground: FVec2D,
+        pivot: FVec2D,
+    ) -> Self {
+        let mut diagnostics = Self {
+            time,
+            ..Self::default()
+        };
+        for i in 0..circles.count() {
+            let inverse_mass = circles.inverse_mass(i);
+            // static circles have no finite energy or momentum to add
+            if inverse_mass == 0.0 {
+                continue;
+            }
+            let (mass, center, speed) = (1.0 / inverse_mass, circles.center(i), circles.speed(i));
+            diagnostics.kinetic_energy += 0.5 * mass * speed.squared_length();
+            diagnostics.potential_energy += mass * FVec2D::dot(gravity, ground - center);
+            diagnostics.linear_momentum += speed * mass;
+            diagnostics.angular_momentum += mass * FVec2D::cross(center - pivot, speed);
+        }
+        diagnostics
+    }
+
+    /// Kinetic plus potential energy
+    pub fn total_energy(&self) -> f32 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    ///
+    /// Draw the values as text
+    ///     origin -> top left corner of the text
+    ///     scale -> size of each font pixel
+    ///
+    pub fn draw_overlay(&self, canvas: &mut Canvas, origin: Point2D, scale: i32, color: Color) {
+        let lines = [
+            format!("T  {:.2}", self.time),
+            format!("KE {:.4e}", self.kinetic_energy),
+            format!("PE {:.4e}", self.potential_energy),
+            format!("E  {:.4e}", self.total_energy()),
+            format!(
+                "P  {:.3e} {:.3e}",
+                self.linear_momentum.x, self.linear_momentum.y
+            ),
+            format!("L  {:.4e}", self.angular_momentum),
+        ];
+        canvas.text(origin, &lines.join("\n"), color, scale);
+    }
+}
+
+// Picks the value of a curve out of a sample
+type Series = fn(&Diagnostics) -> f32;
+
+/// Rolling record of the last few diagnostics, oldest first
+pub struct DiagnosticsHistory {
+    capacity: usize,
+    samples: VecDeque<Diagnostics>,
+}
+
+impl DiagnosticsHistory {
+    /// Create a history that keeps at most capacity samples
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Record a sample dropping the oldest one when full
+    pub fn push(&mut self, diagnostics: Diagnostics) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(diagnostics);
+    }
+
+    pub fn samples(&self) -> &VecDeque<Diagnostics> {
+        &self.samples
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// All samples as comma separated values, one per line with a header on top
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "time,kinetic_energy,potential_energy,total_energy,momentum_x,momentum_y,angular_momentum\n",
+        );
+        for sample in &self.samples {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                sample.time,
+                sample.kinetic_energy,
+                sample.potential_energy,
+                sample.total_energy(),
+                sample.linear_momentum.x,
+                sample.linear_momentum.y,
+                sample.angular_momentum
+            ));
+        }
+        csv
+    }
+
+    /// Write all samples to a CSV file
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_csv().as_bytes())
+    }
+
+    ///
+    /// Draw a scrolling graph of the recorded samples, newest on the right
+    ///     origin -> top left corner of the graph
+    ///     width, height -> size of the graph
+    ///
+    /// Energies share one scale so they can be compared, momentum curves are scaled on their own.
+    ///     red -> kinetic energy
+    ///     blue -> potential energy
+    ///     white -> total energy
+    ///     green -> linear momentum magnitude
+    ///     yellow -> angular momentum
+    ///
+    pub fn draw_graph(&self, canvas: &mut Canvas, origin: Point2D, width: i32, height: i32) {
+        canvas.filled_rectangle(origin, width, height, Color::rgb(20, 20, 20));
+        canvas.rectangle(origin, width, height, color::GRAY);
+        if self.samples.len() < 2 {
+            return;
+        }
+
+        let energy_scale = self
+            .samples
+            .iter()
+            .map(|s| {
+                s.kinetic_energy
+                    .abs()
+                    .max(s.potential_energy.abs())
+                    .max(s.total_energy().abs())
+            })
+            .fold(0.0, f32::max);
+        let momentum_scale = self
+            .samples
+            .iter()
+            .map(|s| s.linear_momentum.length())
+            .fold(0.0, f32::max);
+        let angular_scale = self
+            .samples
+            .iter()
+            .map(|s| s.angular_momentum.abs())
+            .fold(0.0, f32::max);
+
+        let series: [(Series, f32, Color); 5] = [
+            (|s| s.kinetic_energy, energy_scale, color::RED),
+            (|s| s.potential_energy, energy_scale, color::BLUE),
+            (|s| s.total_energy(), energy_scale, color::WHITE),
+            (|s| s.linear_momentum.length(), momentum_scale, color::GREEN),
+            (|s| s.angular_momentum, angular_scale, color::YELLOW),
+        ];
+
+        // values are centered vertically so negative values fit too
+        let middle = origin.y + height / 2;
+        canvas.line(
+            origin.x,
+            middle,
+            origin.x + width,
+            middle,
+            Color::rgb(60, 60, 60),
+        );
+        let count = self.samples.len() as i32;
+        let step = width as f32 / (self.capacity.max(2) - 1) as f32;
+        let start = width - ((count - 1) as f32 * step) as i32;
+        // curves stay inside the frame even when a value jumps past its scale
+        let inside = ClipRect::new(
+            origin.x + 1,
+            origin.y + 1,
+            (width - 1).max(0) as u32,
+            (height - 1).max(0) as u32,
+        );
+        canvas.with_clip(inside, |canvas| {
+            for (value, scale, color) in series.iter() {
+                if *scale <= 0.0 {
+                    continue;
+                }
+                let to_point = |i: usize, sample: &Diagnostics| {
+                    let x = origin.x + start + (i as f32 * step) as i32;
+                    let y = middle - (value(sample) / scale * (height / 2 - 1) as f32) as i32;
+                    Point2D::new(x, y)
+                };
+                let mut previous = to_point(0, &self.samples[0]);
+                for (i, sample) in self.samples.iter().enumerate().skip(1) {
+                    let next = to_point(i, sample);
+                    canvas.line_between(previous, next, *color);
+                    previous = next;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,86 @@
+use crate::balls::Circle;
+use crate::grfx::color;
+use crate::grfx::color::Color;
+use crate::math::vector::FVec2D;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+use std::ops::RangeInclusive;
+
+///
+/// Spawns circles into the world at a steady rate, like a fountain or a spark shower.
+/// Every property of a spawned circle is picked at random from its range, whichever end
+/// comes first, the world despawns the circles when their lifetime runs out.
+///
+/// Emitters with the same seed spawn the same circles.
+///
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    pub position: FVec2D,
+    /// Circles spawned per second
+    pub rate: f32,
+    pub radius: RangeInclusive<f32>,
+    /// Direction circles are thrown in, radians clockwise on screen from pointing right
+    pub direction: f32,
+    /// Largest angle in radians between the direction and the way a circle is thrown,
+    /// the sign is ignored
+    pub spread: f32,
+    pub speed: RangeInclusive<f32>,
+    /// Colors to pick from
+    pub colors: Vec<Color>,
+    /// Seconds a circle lives
+    pub lifetime: RangeInclusive<f32>,
+    // fraction of a circle left over from the previous steps
+    owed: f32,
+    rand: StdRng,
+}
+
+impl Emitter {
+    /// Fountain throwing small circles up from position
+    pub fn new(position: FVec2D, rate: f32, seed: u64) -> Self {
+        Self {
+            position,
+            rate,
+            radius: 2.0..=5.0,
+            direction: -PI / 2.0,
+            spread: 0.3,
+            speed: 150.0..=250.0,
+            colors: vec![color::CYAN, color::BLUE, color::WHITE],
+            lifetime: 2.0..=4.0,
+            owed: 0.0,
+            rand: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Circles due over the last delta_t seconds with how long each of them lives
+    pub(crate) fn emit(&mut self, delta_t: f32) -> Vec<(Circle, f32)> {
+        self.owed += self.rate.max(0.0) * delta_t;
+        let count = self.owed.floor();
+        self.owed -= count;
+        (0..count as usize)
+            .map(|_| {
+                let spread = self.spread.abs();
+                let angle = self.direction + self.rand.gen_range(-spread..=spread);
+                let speed = pick(&mut self.rand, &self.speed);
+                let color = if self.colors.is_empty() {
+                    color::WHITE
+                } else {
+                    self.colors[self.rand.gen_range(0..self.colors.len())]
+                };
+                let circle = Circle::new(
+                    self.position,
+                    pick(&mut self.rand, &self.radius),
+                    FVec2D::new(angle.cos(), angle.sin()) * speed,
+                    color,
+                );
+                (circle, pick(&mut self.rand, &self.lifetime))
+            })
+            .collect()
+    }
+}
+
+// Random value in the range, its ends may come in either order
+fn pick(rand: &mut StdRng, range: &RangeInclusive<f32>) -> f32 {
+    let (start, end) = (*range.start(), *range.end());
+    rand.gen_range(start.min(end)..=start.max(end))
+}
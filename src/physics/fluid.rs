@@ -0,0 +1,416 @@
+use crate::grfx::canvas::Canvas;
+use crate::grfx::color::Color;
+use crate::math::vector::FVec2D;
+use crate::physics::broadphase::Grid;
+use crate::physics::parallel;
+use crate::physics::query::Aabb;
+use crate::physics::world::{Boundary, World};
+use std::f32::consts::PI;
+
+///
+/// Liquid made of smoothed particle hydrodynamics (SPH) particles.
+///
+/// Particles push each other apart when packed tighter than the rest density and drag
+/// each other along through viscosity. They collide with edges and circles, pushing circles
+/// around so light circles float and heavy ones sink.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fluid {
+    pub(crate) positions: Vec<FVec2D>,
+    pub(crate) velocities: Vec<FVec2D>,
+    pub(crate) densities: Vec<f32>,
+    /// Distance over which particles feel each other
+    pub smoothing_radius: f32,
+    /// Radius used to collide particles with edges and circles
+    pub particle_radius: f32,
+    pub particle_mass: f32,
+    /// Mass per square pixel the fluid settles at
+    pub rest_density: f32,
+    /// How hard compressed fluid pushes back
+    pub stiffness: f32,
+    pub viscosity: f32,
+}
+
+impl Default for Fluid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A particle and a neighbour found by the broadphase
+enum Pair {
+    Particles(usize, usize),
+    // particle index, circle index
+    Circle(usize, usize),
+}
+
+impl Fluid {
+    /// Water like fluid for particles spawned PARTICLE_SPACING pixels apart
+    pub fn new() -> Self {
+        let spacing = Self::PARTICLE_SPACING;
+        let mut fluid = Self {
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            densities: Vec::new(),
+            smoothing_radius: 2.0 * spacing,
+            particle_radius: spacing / 2.0,
+            particle_mass: 1.0,
+            rest_density: 1.0,
+            stiffness: 100000.0,
+            viscosity: 50.0,
+        };
+        // mass giving the rest density inside a block of particles laid out by fill
+        let reach = (fluid.smoothing_radius / spacing).ceil() as i32;
+        let mut density = 0.0;
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                density += fluid.density_kernel(((x * x + y * y) as f32) * spacing * spacing);
+            }
+        }
+        fluid.particle_mass = fluid.rest_density / density;
+        fluid
+    }
+
+    /// Distance between particles the default settings are tuned for
+    pub const PARTICLE_SPACING: f32 = 4.0;
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Add a particle and return its index
+    pub fn spawn(&mut self, position: FVec2D, velocity: FVec2D) -> usize {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.densities.push(self.rest_density);
+        self.len() - 1
+    }
+
+    /// Fill a rectangle with particles laid out on a grid
+    pub fn fill(&mut self, min: FVec2D, max: FVec2D) {
+        let spacing = Self::PARTICLE_SPACING;
+        let mut y = min.y;
+        while y <= max.y {
+            let mut x = min.x;
+            while x <= max.x {
+                self.spawn(FVec2D::new(x, y), FVec2D::new(0.0, 0.0));
+                x += spacing;
+            }
+            y += spacing;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.densities.clear();
+    }
+
+    pub fn positions(&self) -> &[FVec2D] {
+        &self.positions
+    }
+
+    pub fn velocities(&self) -> &[FVec2D] {
+        &self.velocities
+    }
+
+    /// Density around each particle as of the last step
+    pub fn densities(&self) -> &[f32] {
+        &self.densities
+    }
+
+    ///
+    /// Draw every particle colored by its density:
+    /// dark blue where the fluid is thin, light blue at rest density and white when compressed.
+    ///
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let radius = self.particle_radius.ceil() as i32;
+        for (position, density) in self.positions.iter().zip(self.densities.iter()) {
+            let color = density_color(density / self.rest_density);
+            canvas.filled_circle(position.to_i32(), radius, color);
+        }
+    }
+
+    // Smoothing kernels for 2D from Müller et al. 2003, Particle-Based Fluid Simulation
+    // for Interactive Applications
+
+    // Poly6 kernel, used for the density
+    fn density_kernel(&self, squared_distance: f32) -> f32 {
+        let h2 = self.smoothing_radius * self.smoothing_radius;
+        if squared_distance >= h2 {
+            return 0.0;
+        }
+        4.0 / (PI * h2.powi(4)) * (h2 - squared_distance).powi(3)
+    }
+
+    // Magnitude of the spiky kernel gradient, used for the pressure
+    fn pressure_kernel(&self, distance: f32) -> f32 {
+        let h = self.smoothing_radius;
+        if distance >= h {
+            return 0.0;
+        }
+        -30.0 / (PI * h.powi(5)) * (h - distance) * (h - distance)
+    }
+
+    // Laplacian of the viscosity kernel
+    fn viscosity_kernel(&self, distance: f32) -> f32 {
+        let h = self.smoothing_radius;
+        if distance >= h {
+            return 0.0;
+        }
+        40.0 / (PI * h.powi(5)) * (h - distance)
+    }
+}
+
+// Blend from thin to compressed fluid colors
+fn density_color(ratio: f32) -> Color {
+    let stops = [
+        (0.5, Color::rgb(10, 30, 120)),
+        (1.0, Color::rgb(40, 120, 230)),
+        (1.3, Color::rgb(230, 245, 255)),
+    ];
+    if ratio <= stops[0].0 {
+        return stops[0].1;
+    }
+    for pair in stops.windows(2) {
+        let ((start, from), (end, to)) = (pair[0], pair[1]);
+        if ratio <= end {
+            let t = (ratio - start) / (end - start);
+            let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+            return Color::rgb(
+                mix(from.r(), to.r()),
+                mix(from.g(), to.g()),
+                mix(from.b(), to.b()),
+            );
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+///
+/// Advance the fluid of the world by delta_t in the same number of sub steps as the circles.
+///
+/// Particles and circles go into one broadphase grid, which finds the particles near each
+/// particle and the circles they touch. Circles are pushed by the particles hitting them.
+///
+pub(crate) fn step(world: &mut World, delta_t: f32) {
+    let sub_step = delta_t / world.simulation_updates as f32;
+    for _ in 0..world.simulation_updates {
+        let pairs = find_pairs(world);
+        compute_densities(&mut world.fluid, &pairs);
+        let accelerations = accelerations(&world.fluid, &pairs, world.gravity);
+        let fluid = &mut world.fluid;
+        for ((position, velocity), acceleration) in fluid
+            .positions
+            .iter_mut()
+            .zip(fluid.velocities.iter_mut())
+            .zip(accelerations.iter())
+        {
+            *velocity += *acceleration * sub_step;
+            *position += *velocity * sub_step;
+        }
+        collide_with_edges(world);
+        collide_with_circles(world, &pairs);
+        match world.boundary {
+            Boundary::Wrap => wrap(world),
+            Boundary::Open => remove_outside(world),
+        }
+    }
+}
+
+// Particle pairs closer than the smoothing radius and particles touching circles
+fn find_pairs(world: &mut World) -> Vec<Pair> {
+    let fluid = &world.fluid;
+    let circles = &world.circles;
+    let h = fluid.smoothing_radius;
+    let particles = fluid.len();
+    // particles first, circles after them
+    let half = FVec2D::new(h / 2.0, h / 2.0);
+    let mut bounds: Vec<Aabb> = fluid
+        .positions
+        .iter()
+        .map(|&p| Aabb {
+            min: p - half,
+            max: p + half,
+        })
+        .collect();
+    bounds.extend(
+        circles
+            .bounds(0.0)
+            .iter()
+            .map(|aabb| aabb.expanded(fluid.particle_radius)),
+    );
+    let grid = Grid::new(&bounds, h);
+
+    let pairs = parallel::flat_map(&fluid.positions, world.threads, |i, &position, pairs| {
+        grid.candidates(i, &bounds, |j| {
+            if j < particles {
+                if (fluid.positions[j] - position).squared_length() < h * h {
+                    pairs.push(Pair::Particles(i, j));
+                }
+            } else {
+                let k = j - particles;
+                let reach = circles.radii[k] + fluid.particle_radius;
+                if (circles.positions[k] - position).squared_length() < reach * reach {
+                    pairs.push(Pair::Circle(i, k));
+                }
+            }
+        });
+    });
+    world.stats.pairs_tested += pairs.len();
+    pairs
+}
+
+fn compute_densities(fluid: &mut Fluid, pairs: &[Pair]) {
+    let own = fluid.particle_mass * fluid.density_kernel(0.0);
+    let mut densities = vec![own; fluid.len()];
+    for pair in pairs {
+        if let Pair::Particles(i, j) = *pair {
+            let squared_distance = (fluid.positions[i] - fluid.positions[j]).squared_length();
+            let density = fluid.particle_mass * fluid.density_kernel(squared_distance);
+            densities[i] += density;
+            densities[j] += density;
+        }
+    }
+    fluid.densities = densities;
+}
+
+// Gravity, pressure and viscosity acting on each particle
+fn accelerations(fluid: &Fluid, pairs: &[Pair], gravity: FVec2D) -> Vec<FVec2D> {
+    // only compressed fluid pushes, pulling particles together makes them clump
+    let pressures: Vec<f32> = fluid
+        .densities
+        .iter()
+        .map(|&density| fluid.stiffness * (density - fluid.rest_density).max(0.0))
+        .collect();
+    let mut forces = vec![FVec2D::new(0.0, 0.0); fluid.len()];
+    for pair in pairs {
+        if let Pair::Particles(i, j) = *pair {
+            let offset = fluid.positions[i] - fluid.positions[j];
+            let distance = offset.length();
+            let direction = if distance > 0.0 {
+                offset / distance
+            } else {
+                // particles on top of each other are pushed apart sideways
+                FVec2D::new(1.0, 0.0)
+            };
+            let (density_i, density_j) = (fluid.densities[i], fluid.densities[j]);
+            let pressure = -fluid.particle_mass * (pressures[i] + pressures[j]) / (2.0 * density_j)
+                * fluid.pressure_kernel(distance);
+            let viscosity = fluid.viscosity
+                * fluid.particle_mass
+                * fluid.viscosity_kernel(distance)
+                * ((density_i + density_j) / (2.0 * density_i * density_j));
+            let relative = fluid.velocities[j] - fluid.velocities[i];
+            let force = direction * pressure + relative * viscosity;
+            forces[i] += force;
+            forces[j] -= force;
+        }
+    }
+    forces
+        .iter()
+        .zip(fluid.densities.iter())
+        .map(|(&force, &density)| force / density + gravity)
+        .collect()
+}
+
+// Push particles out of the edges and stop them moving into the edges
+fn collide_with_edges(world: &mut World) {
+    let fluid = &mut world.fluid;
+    let radius = fluid.particle_radius;
+    for edge in world.lines.iter() {
+        let aabb = Aabb::from_edge(edge).expanded(radius);
+        for (position, velocity) in fluid.positions.iter_mut().zip(fluid.velocities.iter_mut()) {
+            if !aabb.contains(*position) {
+                continue;
+            }
+            let offset = *position - edge.closest_point(*position);
+            let distance = offset.length();
+            let reach = edge.radius + radius;
+            if distance >= reach || distance == 0.0 {
+                continue;
+            }
+            let normal = offset / distance;
+            *position += normal * (reach - distance);
+            let approach = FVec2D::dot(*velocity, normal);
+            if approach < 0.0 {
+                *velocity -= normal * approach;
+            }
+        }
+    }
+}
+
+// Particles and circles push each other apart in proportion to their masses
+fn collide_with_circles(world: &mut World, pairs: &[Pair]) {
+    let fluid = &mut world.fluid;
+    let circles = &mut world.circles;
+    let particle_weight = 1.0 / fluid.particle_mass;
+    for pair in pairs {
+        if let Pair::Circle(i, k) = *pair {
+            let circle_weight = circles.inverse_masses[k];
+            let total = particle_weight + circle_weight;
+            let offset = fluid.positions[i] - circles.positions[k];
+            let distance = offset.length();
+            let reach = circles.radii[k] + fluid.particle_radius;
+            if distance >= reach || distance == 0.0 {
+                continue;
+            }
+            let normal = offset / distance;
+            let correction = normal * ((reach - distance) / total);
+            fluid.positions[i] += correction * particle_weight;
+            circles.positions[k] -= correction * circle_weight;
+
+            let approach = FVec2D::dot(fluid.velocities[i] - circles.velocities[k], normal);
+            if approach < 0.0 {
+                let impulse = normal * (-approach / total);
+                fluid.velocities[i] += impulse * particle_weight;
+                circles.velocities[k] -= impulse * circle_weight;
+            }
+        }
+    }
+}
+
+// Particles completely outside an open world are gone for good
+fn remove_outside(world: &mut World) {
+    let (width, height) = (world.width, world.height);
+    let radius = world.fluid.particle_radius;
+    let fluid = &mut world.fluid;
+    let mut k = 0;
+    while k < fluid.positions.len() {
+        let position = fluid.positions[k];
+        if position.x < -radius
+            || position.y < -radius
+            || position.x > width + radius
+            || position.y > height + radius
+        {
+            fluid.positions.swap_remove(k);
+            fluid.velocities.swap_remove(k);
+            fluid.densities.swap_remove(k);
+        } else {
+            k += 1;
+        }
+    }
+}
+
+// Particles leaving the world come back on the other side, like circles
+fn wrap(world: &mut World) {
+    let (width, height) = (world.width, world.height);
+    for position in world.fluid.positions.iter_mut() {
+        if position.x < 0.0 {
+            position.x += width;
+        }
+        if position.y < 0.0 {
+            position.y += height;
+        }
+        if position.x > width {
+            position.x -= width;
+        }
+        if position.y > height {
+            position.y -= height;
+        }
+    }
+}
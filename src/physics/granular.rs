@@ -0,0 +1,186 @@
+use crate::balls::Circle;
+use crate::math::vector::FVec2D;
+use crate::physics::collision::Response;
+
+///
+/// Settings that make circles behave like sand: touching circles grip each other
+/// so piles keep their slope instead of flowing flat.
+/// Applied on top of the contact response of the impulse solver. Friction acts on the surfaces
+/// where circles touch, so it sets them spinning, and rolling resistance slows that spin down.
+/// Cohesion is a separate pull, applied once per sub step to every pair within its range.
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Granular {
+    /// Coulomb friction coefficient, tangential impulse allowed per unit of normal impulse
+    pub friction: f32,
+    /// Rolling friction coefficient: torque resisting grains rolling over each other allowed
+    /// per unit of normal impulse and of the effective radius of the two circles
+    pub rolling_resistance: f32,
+    /// Acceleration pulling circles within the cohesion range together, 0 for dry sand
+    pub cohesion: f32,
+    /// How far apart circles can be and still pull on each other
+    pub cohesion_range: f32,
+}
+
+impl Granular {
+    /// Dry sand, poured piles hold a slope of about 25 degrees
+    pub fn sand() -> Self {
+        Self {
+            friction: 0.6,
+            rolling_resistance: 2.0,
+            cohesion: 0.0,
+            cohesion_range: 0.0,
+        }
+    }
+
+    /// Wet sand, grains stick together and piles hold steeper slopes
+    pub fn wet_sand() -> Self {
+        Self {
+            friction: 0.8,
+            rolling_resistance: 2.0,
+            cohesion: 300.0,
+            cohesion_range: 1.0,
+        }
+    }
+
+    ///
+    /// Add friction and rolling resistance to the response of two circles in contact.
+    /// Circles the response leaves alone keep it.
+    ///
+    /// Returns the response with the new angular speeds of (first, second)
+    ///
+    pub fn contact(
+        &self,
+        first: &Circle,
+        second: &Circle,
+        response: Response,
+    ) -> (Response, f32, f32) {
+        self.grip(
+            (first, 1.0 / first.mass),
+            (second, 1.0 / second.mass),
+            response,
+        )
+    }
+
+    ///
+    /// Same as contact for a circle touching an edge, represented by the fake circle
+    /// sitting on the closest point of the edge. The edge never moves or turns.
+    ///
+    /// Returns the response with the new angular speed of the circle
+    ///
+    pub fn edge(&self, circle: &Circle, edge: &Circle, response: Response) -> (Response, f32) {
+        let (response, angular_speed, _) =
+            self.grip((circle, 1.0 / circle.mass), (&still(edge), 0.0), response);
+        (response, angular_speed)
+    }
+
+    ///
+    /// Cohesion pulling two circles within the cohesion range together over delta_t,
+    /// whichever way they are moving. Meant to be applied once per step.
+    ///
+    /// Returns the change of speed of (first, second), None when there is no pull
+    ///
+    pub fn pull(&self, first: &Circle, second: &Circle, delta_t: f32) -> Option<(FVec2D, FVec2D)> {
+        self.cohere(
+            (first, 1.0 / first.mass),
+            (second, 1.0 / second.mass),
+            delta_t,
+        )
+    }
+
+    /// Same as pull for a circle near an edge, represented by the fake circle
+    /// sitting on the closest point of the edge
+    pub fn pull_edge(&self, circle: &Circle, edge: &Circle, delta_t: f32) -> Option<FVec2D> {
+        self.cohere((circle, 1.0 / circle.mass), (&still(edge), 0.0), delta_t)
+            .map(|(change, _)| change)
+    }
+
+    // (circle, inverse mass) of each body
+    fn cohere(
+        &self,
+        (first, w1): (&Circle, f32),
+        (second, w2): (&Circle, f32),
+        delta_t: f32,
+    ) -> Option<(FVec2D, FVec2D)> {
+        let distance = second.center - first.center;
+        let gap = distance.length() - first.radius - second.radius;
+        if self.cohesion == 0.0
+            || gap > self.cohesion_range.max(0.0)
+            || distance.squared_length() == 0.0
+            || w1 + w2 == 0.0
+        {
+            return None;
+        }
+        let normal = distance.unit_vector();
+        let impulse = self.cohesion * delta_t / (w1 + w2);
+        Some((normal * (impulse * w1), normal * (-impulse * w2)))
+    }
+
+    // Works on the speeds after the response, (circle, inverse mass) of each body
+    fn grip(
+        &self,
+        (first, w1): (&Circle, f32),
+        (second, w2): (&Circle, f32),
+        response: Response,
+    ) -> (Response, f32, f32) {
+        let unchanged = (response, first.angular_speed, second.angular_speed);
+        let (mut speed1, mut speed2) = match response {
+            Response::None => return unchanged,
+            Response::Resting(speed1, speed2) | Response::Bounce(speed1, speed2) => {
+                (speed1, speed2)
+            }
+        };
+        let distance = second.center - first.center;
+        if distance.squared_length() == 0.0 || w1 + w2 == 0.0 {
+            return unchanged;
+        }
+        let normal = distance.unit_vector();
+
+        // normal impulse of the response, found from how much it changed the speeds
+        let normal_impulse = if w1 > 0.0 {
+            FVec2D::dot(first.speed - speed1, normal).abs() / w1
+        } else {
+            FVec2D::dot(speed2 - second.speed, normal).abs() / w2
+        };
+        if normal_impulse <= 0.0 {
+            return unchanged;
+        }
+
+        // inverse moments of inertia of solid discs
+        let (r1, r2) = (first.radius, second.radius);
+        let (i1, i2) = (2.0 * w1 / (r1 * r1), 2.0 * w2 / (r2 * r2));
+        let (mut spin1, mut spin2) = (first.angular_speed, second.angular_speed);
+
+        // the surfaces slide past each other at the contact point by the speeds and the spins
+        let tangent = normal.perpendicular();
+        let sliding = FVec2D::dot(speed1 - speed2, tangent) + spin1 * r1 + spin2 * r2;
+        let weight = w1 + w2 + r1 * r1 * i1 + r2 * r2 * i2;
+        let limit = self.friction * normal_impulse;
+        let friction = (sliding / weight).clamp(-limit, limit);
+        speed1 -= tangent * (friction * w1);
+        speed2 += tangent * (friction * w2);
+        spin1 -= friction * r1 * i1;
+        spin2 -= friction * r2 * i2;
+
+        // circles rolling over each other turn the opposite way, a torque holds that back
+        let limit = self.rolling_resistance * r1 * r2 / (r1 + r2) * normal_impulse;
+        let torque = ((spin1 - spin2) / (i1 + i2)).clamp(-limit, limit);
+        spin1 -= torque * i1;
+        spin2 += torque * i2;
+
+        let response = match response {
+            Response::Resting(_, _) => Response::Resting(speed1, speed2),
+            _ => Response::Bounce(speed1, speed2),
+        };
+        (response, spin1, spin2)
+    }
+}
+
+// Edges never move or turn
+fn still(edge: &Circle) -> Circle {
+    Circle {
+        speed: FVec2D::new(0.0, 0.0),
+        angular_speed: 0.0,
+        ..*edge
+    }
+}
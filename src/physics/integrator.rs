@@ -0,0 +1,64 @@
+use crate::math::vector::FVec2D;
+
+///
+/// How bodies are moved forward in time under the forces acting on them.
+///
+/// All three are symplectic: energy wobbles around the right value instead of drifting away,
+/// but the Verlet variants keep it much closer for the same time step.
+///
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Integrator {
+    /// Update the speed first and move with the new speed. Cheapest, one force evaluation
+    #[default]
+    SemiImplicitEuler,
+    /// Half a speed update, a full move and another half speed update (kick, drift, kick).
+    /// Exact for constant accelerations like gravity
+    VelocityVerlet,
+    /// Half a move, a full speed update and another half move (drift, kick, drift)
+    PositionVerlet,
+}
+
+impl Integrator {
+    ///
+    /// Advance a body by delta_t
+    ///     position, velocity -> state of the body, updated in place
+    ///     acceleration -> acceleration of a body at the given position with the given velocity
+    ///
+    /// Returns the last acceleration that was evaluated
+    ///
+    pub fn advance<F>(
+        self,
+        position: &mut FVec2D,
+        velocity: &mut FVec2D,
+        delta_t: f32,
+        acceleration: F,
+    ) -> FVec2D
+    where
+        F: Fn(FVec2D, FVec2D) -> FVec2D,
+    {
+        match self {
+            Integrator::SemiImplicitEuler => {
+                let a = acceleration(*position, *velocity);
+                *velocity += a * delta_t;
+                *position += *velocity * delta_t;
+                a
+            }
+            Integrator::VelocityVerlet => {
+                let half_t = delta_t / 2.0;
+                *velocity += acceleration(*position, *velocity) * half_t;
+                *position += *velocity * delta_t;
+                let a = acceleration(*position, *velocity);
+                *velocity += a * half_t;
+                a
+            }
+            Integrator::PositionVerlet => {
+                let half_t = delta_t / 2.0;
+                *position += *velocity * half_t;
+                let a = acceleration(*position, *velocity);
+                *velocity += a * delta_t;
+                *position += *velocity * half_t;
+                a
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::math::vector::FVec2D;
+use crate::physics::bodies::Bodies;
+
+///
+/// Pins two circles together at a point they both turn around, like an axle or a hinge.
+/// Only enforced by the XPBD solver.
+///     first_anchor, second_anchor -> the pin relative to the center of each circle,
+///                                    before the circle is rotated
+///     reference_angle -> angle of the second circle relative to the first when the joint was made
+///     limits -> lowest and highest angle the second circle may turn to relative to the first,
+///               measured from the reference angle. None for a joint that turns freely
+///     motor -> turns the second circle relative to the first, None for a joint without a motor
+///     break_force, break_torque -> the joint snaps when holding the pin or the limits takes more,
+///                                  None for joints that never break
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RevoluteJoint {
+    pub first: usize,
+    pub second: usize,
+    pub first_anchor: FVec2D,
+    pub second_anchor: FVec2D,
+    pub reference_angle: f32,
+    pub limits: Option<(f32, f32)>,
+    pub motor: Option<Motor>,
+    pub break_force: Option<f32>,
+    pub break_torque: Option<f32>,
+}
+
+///
+/// Drives a revolute joint towards a relative turning speed
+///     target_speed -> radians per second, positive turns the second circle clockwise on screen
+///     max_torque -> strongest torque the motor can apply
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Motor {
+    pub target_speed: f32,
+    pub max_torque: f32,
+}
+
+impl RevoluteJoint {
+    /// Pin two circles together at the given point, keeping their current angles
+    pub fn new(circles: &Bodies, first: usize, second: usize, pin: FVec2D) -> Self {
+        let (first_angle, second_angle) = (circles.angles[first], circles.angles[second]);
+        Self {
+            first,
+            second,
+            first_anchor: rotate(pin - circles.positions[first], -first_angle),
+            second_anchor: rotate(pin - circles.positions[second], -second_angle),
+            reference_angle: second_angle - first_angle,
+            limits: None,
+            motor: None,
+            break_force: None,
+            break_torque: None,
+        }
+    }
+
+    /// Same joint, only turning between lower and upper radians. Equal limits lock the joint
+    pub fn with_limits(self, lower: f32, upper: f32) -> Self {
+        Self {
+            limits: Some((lower, upper)),
+            ..self
+        }
+    }
+
+    /// Same joint, driven by a motor
+    pub fn with_motor(self, target_speed: f32, max_torque: f32) -> Self {
+        Self {
+            motor: Some(Motor {
+                target_speed,
+                max_torque,
+            }),
+            ..self
+        }
+    }
+
+    /// Same joint, snapping when holding the pin takes more than force
+    pub fn with_break_force(self, force: f32) -> Self {
+        Self {
+            break_force: Some(force),
+            ..self
+        }
+    }
+
+    /// Same joint, snapping when holding the limits or driving the motor takes more than torque
+    pub fn with_break_torque(self, torque: f32) -> Self {
+        Self {
+            break_torque: Some(torque),
+            ..self
+        }
+    }
+
+    /// Angle of the second circle relative to the first, measured from the reference angle
+    pub fn angle(&self, circles: &Bodies) -> f32 {
+        circles.angles[self.second] - circles.angles[self.first] - self.reference_angle
+    }
+
+    /// Where the pin is on each circle, in world coordinates
+    pub fn pins(&self, circles: &Bodies) -> (FVec2D, FVec2D) {
+        (
+            circles.positions[self.first] + rotate(self.first_anchor, circles.angles[self.first]),
+            circles.positions[self.second]
+                + rotate(self.second_anchor, circles.angles[self.second]),
+        )
+    }
+}
+
+/// Impulses a joint applied during one sub step, used to find out whether it breaks
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub(crate) struct JointLoad {
+    // summed position corrections of the pin, the limits and the motor, scaled by mass or inertia
+    pub(crate) pin: f32,
+    pub(crate) limit: f32,
+    // signed, so the motor can be held to its torque
+    pub(crate) motor: f32,
+}
+
+impl JointLoad {
+    /// Force and torque the joint held during a sub step of delta_t
+    pub(crate) fn force_and_torque(&self, delta_t: f32) -> (f32, f32) {
+        let h2 = delta_t * delta_t;
+        (self.pin / h2, (self.limit + self.motor.abs()) / h2)
+    }
+}
+
+// Rotate a vector by radians, clockwise on screen
+pub(crate) fn rotate(vector: FVec2D, radians: f32) -> FVec2D {
+    let (sin, cos) = radians.sin_cos();
+    FVec2D::new(
+        vector.x * cos - vector.y * sin,
+        vector.x * sin + vector.y * cos,
+    )
+}
+
+///
+/// Move and turn the circles of every joint so the pins meet, the angles stay within the limits
+/// and motors turn by their target speed over the sub step of delta_t.
+/// Each correction is shared between the two circles by their inverse mass and inertia.
+///
+/// Motors work on the angles like the limits do, so the circles a motor turns against are held
+/// by the rest of the joints while it turns. Over a sub step a motor turns the circles no further
+/// than its torque can.
+///
+pub(crate) fn solve_joints(
+    circles: &mut Bodies,
+    joints: &[RevoluteJoint],
+    loads: &mut [JointLoad],
+    delta_t: f32,
+) {
+    for (joint, load) in joints.iter().zip(loads.iter_mut()) {
+        let (i, j) = (joint.first, joint.second);
+        let (w1, w2) = (circles.inverse_masses[i], circles.inverse_masses[j]);
+        let (i1, i2) = (circles.inverse_inertia(i), circles.inverse_inertia(j));
+
+        let (pin1, pin2) = joint.pins(circles);
+        let offset = pin2 - pin1;
+        let distance = offset.length();
+        if distance > 0.0 {
+            let normal = offset / distance;
+            let (r1, r2) = (pin1 - circles.positions[i], pin2 - circles.positions[j]);
+            let (arm1, arm2) = (FVec2D::cross(r1, normal), FVec2D::cross(r2, normal));
+            let weight = w1 + w2 + arm1 * arm1 * i1 + arm2 * arm2 * i2;
+            if weight > 0.0 {
+                let lambda = distance / weight;
+                let impulse = normal * lambda;
+                circles.positions[i] += impulse * w1;
+                circles.angles[i] += FVec2D::cross(r1, impulse) * i1;
+                circles.positions[j] -= impulse * w2;
+                circles.angles[j] -= FVec2D::cross(r2, impulse) * i2;
+                load.pin += lambda;
+            }
+        }
+        if i1 + i2 == 0.0 {
+            continue;
+        }
+
+        if let Some((lower, upper)) = joint.limits {
+            let angle = joint.angle(circles);
+            let error = if angle < lower {
+                angle - lower
+            } else if angle > upper {
+                angle - upper
+            } else {
+                0.0
+            };
+            if error != 0.0 {
+                let lambda = error / (i1 + i2);
+                circles.angles[i] += lambda * i1;
+                circles.angles[j] -= lambda * i2;
+                load.limit += lambda.abs();
+            }
+        }
+
+        if let Some(motor) = joint.motor {
+            let turned = (circles.angles[j] - circles.prev_angles[j])
+                - (circles.angles[i] - circles.prev_angles[i]);
+            let error = turned - motor.target_speed * delta_t;
+            let limit = motor.max_torque * delta_t * delta_t;
+            let total = (load.motor + error / (i1 + i2)).clamp(-limit, limit);
+            let lambda = total - load.motor;
+            circles.angles[i] += lambda * i1;
+            circles.angles[j] -= lambda * i2;
+            load.motor = total;
+        }
+    }
+}
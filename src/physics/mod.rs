@@ -0,0 +1,16 @@
+pub mod bodies;
+pub mod broadphase;
+pub mod cluster;
+pub mod collision;
+pub mod diagnostics;
+pub mod emitter;
+pub mod fluid;
+pub mod granular;
+pub mod integrator;
+pub mod joint;
+pub mod parallel;
+pub mod query;
+#[cfg(test)]
+mod test;
+pub mod world;
+pub mod xpbd;
@@ -0,0 +1,115 @@
+use std::thread;
+
+/// Smallest amount of items worth handing to a thread of its own.
+/// Smaller workloads run on the calling thread since starting threads costs more than the work.
+pub const MIN_CHUNK: usize = 512;
+
+/// Number of threads available on this machine
+pub fn available_threads() -> usize {
+    thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
+
+/// Items per chunk when splitting len items across threads
+pub fn chunk_size(len: usize, threads: usize) -> usize {
+    let threads = threads.max(1);
+    len.div_ceil(threads).max(MIN_CHUNK)
+}
+
+///
+/// Call work on every item, splitting the items across up to threads scoped threads.
+/// work receives the index of the item in the slice and the item itself.
+///
+pub fn for_each_mut<T, F>(items: &mut [T], threads: usize, work: F)
+where
+    T: Send,
+    F: Fn(usize, &mut T) + Sync,
+{
+    let size = chunk_size(items.len(), threads);
+    if items.len() <= size {
+        for (i, item) in items.iter_mut().enumerate() {
+            work(i, item);
+        }
+        return;
+    }
+    let work = &work;
+    thread::scope(|scope| {
+        for (chunk_index, chunk) in items.chunks_mut(size).enumerate() {
+            scope.spawn(move || {
+                for (i, item) in chunk.iter_mut().enumerate() {
+                    work(chunk_index * size + i, item);
+                }
+            });
+        }
+    });
+}
+
+///
+/// Call work on every task, each on a scoped thread of its own.
+/// A single task runs on the calling thread. Used to work on chunks of several
+/// slices at once, see chunk_size.
+///
+pub fn run<T, I, F>(tasks: I, work: F)
+where
+    I: IntoIterator<Item = T>,
+    T: Send,
+    F: Fn(T) + Sync,
+{
+    let mut tasks: Vec<T> = tasks.into_iter().collect();
+    if tasks.len() <= 1 {
+        if let Some(task) = tasks.pop() {
+            work(task);
+        }
+        return;
+    }
+    let work = &work;
+    thread::scope(|scope| {
+        for task in tasks {
+            scope.spawn(move || work(task));
+        }
+    });
+}
+
+///
+/// Map every item to any number of results, splitting the items across up to threads scoped threads.
+/// work receives the index of the item in the slice, the item and the list to push results to.
+/// Results come back in the same order as if the items were processed one after another,
+/// no matter how many threads were used.
+///
+pub fn flat_map<T, R, F>(items: &[T], threads: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T, &mut Vec<R>) + Sync,
+{
+    let size = chunk_size(items.len(), threads);
+    if items.len() <= size {
+        let mut results = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            work(i, item, &mut results);
+        }
+        return results;
+    }
+    let work = &work;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(size)
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                scope.spawn(move || {
+                    let mut results = Vec::new();
+                    for (i, item) in chunk.iter().enumerate() {
+                        work(chunk_index * size + i, item, &mut results);
+                    }
+                    results
+                })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for handle in handles {
+            results.extend(handle.join().unwrap());
+        }
+        results
+    })
+}
@@ -0,0 +1,312 @@
+use crate::balls::{Circle, LineSegment};
+use crate::math::vector::FVec2D;
+use crate::physics::bodies::Circles;
+
+/// A body that was found by a spatial query
+/// Circle(i) -> index into the circles slice
+/// Edge(i)   -> index into the line segments slice
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Body {
+    Circle(usize),
+    Edge(usize),
+}
+
+/// One of the two rounded ends of an edge (capsule)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdgeEnd {
+    Start,
+    End,
+}
+
+/// Result of a ray cast or a shape cast
+///     body -> body that was hit
+///     point -> point of contact on the surface of the body
+///     normal -> surface normal at the contact point (unit vector pointing out of the body)
+///     distance -> distance travelled along the cast direction before the hit
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    pub body: Body,
+    pub point: FVec2D,
+    pub normal: FVec2D,
+    pub distance: f32,
+}
+
+/// Axis aligned bounding box
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: FVec2D,
+    pub max: FVec2D,
+}
+
+impl Aabb {
+    pub fn new(min: FVec2D, max: FVec2D) -> Self {
+        Self {
+            min: FVec2D::new(min.x.min(max.x), min.y.min(max.y)),
+            max: FVec2D::new(min.x.max(max.x), min.y.max(max.y)),
+        }
+    }
+
+    /// Bounding box of a circle
+    pub fn from_circle(circle: &Circle) -> Self {
+        Self::around(circle.center, circle.radius)
+    }
+
+    /// Bounding box of a circle given by its center and radius
+    pub fn around(center: FVec2D, radius: f32) -> Self {
+        let extent = FVec2D::new(radius, radius);
+        Self {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+
+    /// Bounding box of an edge including its rounded ends
+    pub fn from_edge(edge: &LineSegment) -> Self {
+        let extent = FVec2D::new(edge.radius, edge.radius);
+        let aabb = Self::new(edge.start, edge.end);
+        Self {
+            min: aabb.min - extent,
+            max: aabb.max + extent,
+        }
+    }
+
+    /// Grow the box by the given amount on every side
+    pub fn expanded(&self, amount: f32) -> Self {
+        let extent = FVec2D::new(amount, amount);
+        Self {
+            min: self.min - extent,
+            max: self.max + extent,
+        }
+    }
+
+    /// Check whether two boxes overlap (touching counts as overlapping)
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// Check whether a point is inside the box
+    pub fn contains(&self, point: FVec2D) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+}
+
+///
+/// Spatial queries over all the bodies in a scene.
+/// Borrows the circles and edges so it is cheap to build one whenever a query is needed.
+/// Circles can be a plain list of circles or the Bodies of a world.
+///
+/// Casts ignore bodies that already contain the origin of the cast, so a circle can
+/// cast itself along its own path without hitting itself.
+///
+pub struct SpatialQuery<'a, C: Circles + ?Sized = [Circle]> {
+    circles: &'a C,
+    edges: &'a [LineSegment],
+}
+
+impl<'a, C: Circles + ?Sized> SpatialQuery<'a, C> {
+    pub fn new(circles: &'a C, edges: &'a [LineSegment]) -> Self {
+        Self { circles, edges }
+    }
+
+    /// All bodies containing the given point
+    pub fn point(&self, point: FVec2D) -> Vec<Body> {
+        let mut bodies = Vec::new();
+        for i in 0..self.circles.count() {
+            if Circle::disc_contains_point(self.circles.center(i), self.circles.radius(i), point) {
+                bodies.push(Body::Circle(i));
+            }
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            if Circle::disc_contains_point(edge.closest_point(point), edge.radius, point) {
+                bodies.push(Body::Edge(i));
+            }
+        }
+        bodies
+    }
+
+    /// Closest rounded edge end containing the given point.
+    /// Used to pick edges by their handles.
+    pub fn edge_end(&self, point: FVec2D) -> Option<(usize, EdgeEnd)> {
+        let mut closest: Option<(usize, EdgeEnd, f32)> = None;
+        for (i, edge) in self.edges.iter().enumerate() {
+            for (end, position) in [(EdgeEnd::Start, edge.start), (EdgeEnd::End, edge.end)].iter() {
+                if !Circle::disc_contains_point(*position, edge.radius, point) {
+                    continue;
+                }
+                let distance = (point - *position).length();
+                if closest.map_or(true, |(_, _, d)| distance < d) {
+                    closest = Some((i, *end, distance));
+                }
+            }
+        }
+        closest.map(|(i, end, _)| (i, end))
+    }
+
+    /// All bodies whose bounding box overlaps the given box
+    pub fn aabb(&self, aabb: &Aabb) -> Vec<Body> {
+        let mut bodies = Vec::new();
+        for i in 0..self.circles.count() {
+            if Aabb::around(self.circles.center(i), self.circles.radius(i)).overlaps(aabb) {
+                bodies.push(Body::Circle(i));
+            }
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            if Aabb::from_edge(edge).overlaps(aabb) {
+                bodies.push(Body::Edge(i));
+            }
+        }
+        bodies
+    }
+
+    ///
+    /// Cast a ray and return the first body it hits
+    ///     origin -> start of the ray
+    ///     direction -> direction of the ray (does not need to be normalized)
+    ///     max_distance -> how far along the ray to look
+    ///
+    pub fn raycast(&self, origin: FVec2D, direction: FVec2D, max_distance: f32) -> Option<Hit> {
+        self.cast(origin, direction, max_distance, 0.0)
+    }
+
+    ///
+    /// Sweep a circle along a direction and return the first body it touches
+    ///     center -> starting position of the swept circle
+    ///     radius -> radius of the swept circle
+    ///     direction -> direction of travel (does not need to be normalized)
+    ///     max_distance -> how far to sweep
+    ///
+    /// The hit point is the contact point on the surface of the body that was hit
+    /// and the swept circle center at the time of impact is center + direction * distance.
+    ///
+    pub fn circle_cast(
+        &self,
+        center: FVec2D,
+        radius: f32,
+        direction: FVec2D,
+        max_distance: f32,
+    ) -> Option<Hit> {
+        self.cast(center, direction, max_distance, radius)
+    }
+
+    // A circle cast is a ray cast against every body inflated by the radius of the swept circle
+    fn cast(
+        &self,
+        origin: FVec2D,
+        direction: FVec2D,
+        max_distance: f32,
+        inflate: f32,
+    ) -> Option<Hit> {
+        if direction.squared_length() == 0.0 {
+            return None;
+        }
+        let direction = direction.unit_vector();
+        let mut best: Option<(Body, f32)> = None;
+
+        for i in 0..self.circles.count() {
+            let (center, radius) = (self.circles.center(i), self.circles.radius(i));
+            if let Some(t) = ray_circle(origin, direction, center, radius + inflate) {
+                if t <= max_distance && best.map_or(true, |(_, d)| t < d) {
+                    best = Some((Body::Circle(i), t));
+                }
+            }
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            if let Some(t) = ray_capsule(
+                origin,
+                direction,
+                edge.start,
+                edge.end,
+                edge.radius + inflate,
+            ) {
+                if t <= max_distance && best.map_or(true, |(_, d)| t < d) {
+                    best = Some((Body::Edge(i), t));
+                }
+            }
+        }
+
+        best.map(|(body, distance)| {
+            let position = origin + direction * distance;
+            let (closest, radius) = match body {
+                Body::Circle(i) => (self.circles.center(i), self.circles.radius(i)),
+                Body::Edge(i) => (self.edges[i].closest_point(position), self.edges[i].radius),
+            };
+            let normal = (position - closest).unit_vector();
+            Hit {
+                body,
+                point: closest + normal * radius,
+                normal,
+                distance,
+            }
+        })
+    }
+}
+
+// Distance along a normalized ray to the surface of a circle.
+// None when it misses or when the origin is already inside the circle.
+fn ray_circle(origin: FVec2D, direction: FVec2D, center: FVec2D, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = FVec2D::dot(offset, direction);
+    let c = offset.squared_length() - radius * radius;
+    if c <= 0.0 || b > 0.0 {
+        return None;
+    }
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+// Distance along a normalized ray to the surface of a capsule (segment start-end inflated by radius).
+// None when it misses or when the origin is already inside the capsule.
+fn ray_capsule(
+    origin: FVec2D,
+    direction: FVec2D,
+    start: FVec2D,
+    end: FVec2D,
+    radius: f32,
+) -> Option<f32> {
+    let segment = end - start;
+    if segment.squared_length() == 0.0 {
+        return ray_circle(origin, direction, start, radius);
+    }
+    let closest = LineSegment::new(start, end, radius).closest_point(origin);
+    if Circle::disc_contains_point(closest, radius, origin) {
+        return None;
+    }
+
+    let mut best: Option<f32> = None;
+    let mut keep = |t: f32| {
+        if best.map_or(true, |b| t < b) {
+            best = Some(t);
+        }
+    };
+
+    // the two flat sides of the capsule
+    let normal = segment.perpendicular().unit_vector() * radius;
+    let denominator = FVec2D::cross(direction, segment);
+    if denominator != 0.0 {
+        for side in [start + normal, start - normal].iter() {
+            let to_side = *side - origin;
+            let t = FVec2D::cross(to_side, segment) / denominator;
+            let s = FVec2D::cross(to_side, direction) / denominator;
+            if t >= 0.0 && (0.0..=1.0).contains(&s) {
+                keep(t);
+            }
+        }
+    }
+    // the two rounded ends
+    if let Some(t) = ray_circle(origin, direction, start, radius) {
+        keep(t);
+    }
+    if let Some(t) = ray_circle(origin, direction, end, radius) {
+        keep(t);
+    }
+    best
+}
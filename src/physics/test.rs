@@ -0,0 +1,1432 @@
+use crate::balls::{Circle, LineSegment};
+use crate::grfx::color;
+use crate::math::vector::FVec2D;
+use crate::physics::bodies::Bodies;
+use crate::physics::broadphase::Grid;
+use crate::physics::collision::{
+    batch_contacts, contact_response, elastic_response, separate_circles, Contact, Response,
+};
+use crate::physics::diagnostics::{Diagnostics, DiagnosticsHistory};
+use crate::physics::emitter::Emitter;
+use crate::physics::granular::Granular;
+use crate::physics::integrator::Integrator;
+use crate::physics::joint::RevoluteJoint;
+use crate::physics::query::{Aabb, Body, EdgeEnd, SpatialQuery};
+use crate::physics::world::{Boundary, Event, Solver, World};
+use crate::physics::xpbd::DistanceConstraint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+fn scene() -> (Vec<Circle>, Vec<LineSegment>) {
+    let circles = vec![
+        Circle::new(
+            FVec2D::new(0.0, 0.0),
+            10.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ),
+        Circle::new(
+            FVec2D::new(100.0, 0.0),
+            10.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ),
+    ];
+    let lines = vec![LineSegment::new(
+        FVec2D::new(-50.0, 100.0),
+        FVec2D::new(150.0, 100.0),
+        5.0,
+    )];
+    (circles, lines)
+}
+
+#[test]
+fn query_point() {
+    let (circles, lines) = scene();
+    let query = SpatialQuery::new(&circles, &lines);
+    assert_eq!(query.point(FVec2D::new(3.0, 4.0)), vec![Body::Circle(0)]);
+    assert_eq!(query.point(FVec2D::new(50.0, 97.0)), vec![Body::Edge(0)]);
+    assert!(query.point(FVec2D::new(50.0, 50.0)).is_empty());
+    assert_eq!(
+        query.edge_end(FVec2D::new(149.0, 101.0)),
+        Some((0, EdgeEnd::End))
+    );
+    assert_eq!(query.edge_end(FVec2D::new(50.0, 100.0)), None);
+}
+
+#[test]
+fn query_raycast() {
+    let (circles, lines) = scene();
+    let query = SpatialQuery::new(&circles, &lines);
+
+    let hit = query
+        .raycast(FVec2D::new(-50.0, 0.0), FVec2D::new(1.0, 0.0), 1000.0)
+        .unwrap();
+    assert_eq!(hit.body, Body::Circle(0));
+    assert_eq!(hit.distance, 40.0);
+    assert_eq!(hit.point, FVec2D::new(-10.0, 0.0));
+    assert_eq!(hit.normal, FVec2D::new(-1.0, 0.0));
+
+    let hit = query
+        .raycast(FVec2D::new(50.0, 0.0), FVec2D::new(0.0, 1.0), 1000.0)
+        .unwrap();
+    assert_eq!(hit.body, Body::Edge(0));
+    assert_eq!(hit.distance, 95.0);
+    assert_eq!(hit.normal, FVec2D::new(0.0, -1.0));
+
+    assert!(query
+        .raycast(FVec2D::new(50.0, 0.0), FVec2D::new(0.0, 1.0), 90.0)
+        .is_none());
+}
+
+#[test]
+fn query_circle_cast() {
+    let (circles, lines) = scene();
+    let query = SpatialQuery::new(&circles, &lines);
+
+    // the first circle sweeps to the right, ignores itself and stops touching the second one
+    let hit = query
+        .circle_cast(circles[0].center, 10.0, FVec2D::new(1.0, 0.0), 1000.0)
+        .unwrap();
+    assert_eq!(hit.body, Body::Circle(1));
+    assert_eq!(hit.distance, 80.0);
+    assert_eq!(hit.point, FVec2D::new(90.0, 0.0));
+
+    // sweeping down lands on the edge
+    let hit = query
+        .circle_cast(circles[0].center, 10.0, FVec2D::new(0.0, 1.0), 1000.0)
+        .unwrap();
+    assert_eq!(hit.body, Body::Edge(0));
+    assert_eq!(hit.distance, 85.0);
+}
+
+#[test]
+fn query_aabb() {
+    let (circles, lines) = scene();
+    let query = SpatialQuery::new(&circles, &lines);
+    let aabb = Aabb::new(FVec2D::new(95.0, -5.0), FVec2D::new(200.0, 96.0));
+    assert_eq!(query.aabb(&aabb), vec![Body::Circle(1), Body::Edge(0)]);
+    assert!(Aabb::from_circle(&circles[0]).contains(FVec2D::new(-10.0, 10.0)));
+}
+
+#[test]
+fn circle_contains_point() {
+    let (circles, _) = scene();
+    assert!(circles[0].contains_point(FVec2D::new(0.0, 0.0)));
+    assert!(circles[0].contains_point(FVec2D::new(6.0, 8.0)));
+    assert!(circles[0].contains_point(FVec2D::new(0.0, -10.0)));
+    assert!(!circles[0].contains_point(FVec2D::new(8.0, 8.0)));
+}
+
+#[test]
+fn circle_signed_distance() {
+    let (circles, _) = scene();
+    assert_eq!(circles[1].signed_distance(FVec2D::new(100.0, 0.0)), -10.0);
+    assert_eq!(circles[1].signed_distance(FVec2D::new(110.0, 0.0)), 0.0);
+    assert_eq!(circles[1].signed_distance(FVec2D::new(130.0, 40.0)), 40.0);
+}
+
+#[test]
+fn circle_closest_point() {
+    let (circles, _) = scene();
+    assert_eq!(
+        circles[0].closest_point(FVec2D::new(0.0, 50.0)),
+        FVec2D::new(0.0, 10.0)
+    );
+    assert_eq!(
+        circles[0].closest_point(FVec2D::new(-3.0, 0.0)),
+        FVec2D::new(-10.0, 0.0)
+    );
+    assert_eq!(
+        circles[0].closest_point(FVec2D::new(0.0, 0.0)),
+        FVec2D::new(10.0, 0.0)
+    );
+}
+
+#[test]
+fn circle_intersects_circle() {
+    let (mut circles, _) = scene();
+    assert!(!circles[0].intersects_circle(&circles[1]));
+    circles[1].center = FVec2D::new(20.0, 0.0);
+    assert!(circles[0].intersects_circle(&circles[1]));
+    assert!(circles[1].intersects_circle(&circles[0]));
+    circles[1].center = FVec2D::new(15.0, 0.0);
+    assert!(circles[0].intersects_circle(&circles[1]));
+}
+
+#[test]
+fn circle_intersects_capsule() {
+    let (mut circles, lines) = scene();
+    assert!(!circles[0].intersects_capsule(&lines[0]));
+    // touching the flat side
+    circles[0].center = FVec2D::new(50.0, 85.0);
+    assert!(circles[0].intersects_capsule(&lines[0]));
+    // near the rounded end but outside of it
+    circles[0].center = FVec2D::new(165.0, 115.0);
+    assert!(!circles[0].intersects_capsule(&lines[0]));
+    circles[0].center = FVec2D::new(160.0, 105.0);
+    assert!(circles[0].intersects_capsule(&lines[0]));
+}
+
+#[test]
+fn diagnostics_measure() {
+    let mut circles = vec![
+        Circle::new(
+            FVec2D::new(10.0, 0.0),
+            1.0,
+            FVec2D::new(0.0, 2.0),
+            color::RED,
+        ),
+        Circle::new(
+            FVec2D::new(0.0, 5.0),
+            1.0,
+            FVec2D::new(3.0, 0.0),
+            color::RED,
+        ),
+    ];
+    circles[0].mass = 2.0;
+    circles[1].mass = 1.0;
+    let diagnostics = Diagnostics::measure(
+        1.5,
+        &circles,
+        FVec2D::new(0.0, 10.0),
+        FVec2D::new(0.0, 10.0),
+        FVec2D::new(0.0, 0.0),
+    );
+    assert_eq!(diagnostics.time, 1.5);
+    assert_eq!(
+        diagnostics.kinetic_energy,
+        0.5 * 2.0 * 4.0 + 0.5 * 1.0 * 9.0
+    );
+    assert_eq!(diagnostics.potential_energy, 2.0 * 100.0 + 1.0 * 50.0);
+    assert_eq!(diagnostics.linear_momentum, FVec2D::new(3.0, 4.0));
+    assert_eq!(diagnostics.angular_momentum, 2.0 * 20.0 - 15.0);
+
+    // a static circle adds nothing, read from a list or from the arrays of a world
+    let mut wall = Circle::new(
+        FVec2D::new(4.0, 4.0),
+        3.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    wall.mass = f32::INFINITY;
+    circles.push(wall);
+    let bodies = Bodies::from(circles.clone());
+    for measured in [
+        Diagnostics::measure(
+            1.5,
+            &circles,
+            FVec2D::new(0.0, 10.0),
+            FVec2D::new(0.0, 10.0),
+            FVec2D::new(0.0, 0.0),
+        ),
+        Diagnostics::measure(
+            1.5,
+            &bodies,
+            FVec2D::new(0.0, 10.0),
+            FVec2D::new(0.0, 10.0),
+            FVec2D::new(0.0, 0.0),
+        ),
+    ]
+    .iter()
+    {
+        assert_eq!(*measured, diagnostics);
+    }
+}
+
+#[test]
+fn diagnostics_history_csv() {
+    let mut history = DiagnosticsHistory::new(2);
+    for i in 0..3 {
+        history.push(Diagnostics {
+            time: i as f32,
+            ..Diagnostics::default()
+        });
+    }
+    assert_eq!(history.len(), 2);
+    let csv = history.to_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("time,kinetic_energy"));
+    assert!(lines[1].starts_with("1,"));
+    assert!(lines[2].starts_with("2,"));
+}
+
+#[test]
+fn world_step_records_diagnostics() {
+    let (circles, lines) = scene();
+    let mut world = World::new(800.0, 600.0);
+    world.circles = circles.into();
+    world.lines = lines;
+    world.step(1.0 / 60.0);
+    world.step(1.0 / 60.0);
+    assert_eq!(world.history.len(), 2);
+    assert_eq!(world.diagnostics, world.measure());
+    assert!(world.diagnostics.kinetic_energy > 0.0);
+}
+
+// Number of random cases each property is checked against
+const CASES: usize = 1000;
+
+// A random circle somewhere near the origin
+fn random_circle(rand: &mut StdRng) -> Circle {
+    let mut circle = Circle::new(
+        FVec2D::new(rand.gen_range(-50.0..50.0), rand.gen_range(-50.0..50.0)),
+        rand.gen_range(1.0..30.0),
+        FVec2D::new(rand.gen_range(-200.0..200.0), rand.gen_range(-200.0..200.0)),
+        color::RED,
+    );
+    circle.mass = rand.gen_range(0.1..500.0);
+    circle
+}
+
+// A random pair of circles that overlap
+fn random_colliding_pair(rand: &mut StdRng) -> (Circle, Circle) {
+    loop {
+        let first = random_circle(rand);
+        let second = random_circle(rand);
+        if first.intersects_circle(&second) && first.center != second.center {
+            return (first, second);
+        }
+    }
+}
+
+fn momentum(circles: &[Circle]) -> FVec2D {
+    circles
+        .iter()
+        .fold(FVec2D::new(0.0, 0.0), |sum, c| sum + c.speed * c.mass)
+}
+
+fn kinetic_energy(circles: &[Circle]) -> f32 {
+    circles
+        .iter()
+        .map(|c| 0.5 * c.mass * c.speed.squared_length())
+        .sum()
+}
+
+// Relative comparison, the values involved span several orders of magnitude
+fn assert_close(expected: f32, actual: f32, scale: f32) {
+    assert!(
+        (expected - actual).abs() <= 1e-4 * scale.max(1.0),
+        "expected {} got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn collision_conserves_momentum() {
+    let mut rand = StdRng::seed_from_u64(1);
+    for _ in 0..CASES {
+        let (mut first, mut second) = random_colliding_pair(&mut rand);
+        let before = momentum(&[first, second]);
+        let (speed1, speed2) = elastic_response(&first, &second);
+        first.speed = speed1;
+        second.speed = speed2;
+        let after = momentum(&[first, second]);
+        let scale = first.mass * 300.0 + second.mass * 300.0;
+        assert_close(before.x, after.x, scale);
+        assert_close(before.y, after.y, scale);
+    }
+}
+
+#[test]
+fn elastic_collision_conserves_kinetic_energy() {
+    let mut rand = StdRng::seed_from_u64(2);
+    for _ in 0..CASES {
+        let (mut first, mut second) = random_colliding_pair(&mut rand);
+        let before = kinetic_energy(&[first, second]);
+        let (speed1, speed2) = elastic_response(&first, &second);
+        first.speed = speed1;
+        second.speed = speed2;
+        assert_close(before, kinetic_energy(&[first, second]), before);
+    }
+}
+
+#[test]
+fn separation_leaves_no_overlap() {
+    let mut rand = StdRng::seed_from_u64(3);
+    for _ in 0..CASES {
+        let (mut first, mut second) = random_colliding_pair(&mut rand);
+        separate_circles(&mut first, &mut second, 0.0);
+        let distance = (first.center - second.center).length();
+        assert_close(first.radius + second.radius, distance, distance);
+        assert!(distance >= (first.radius + second.radius) * (1.0 - 1e-5));
+    }
+
+    // circles sharing a center still get pushed apart
+    let mut first = Circle::new(
+        FVec2D::new(0.0, 0.0),
+        5.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    let mut second = first;
+    separate_circles(&mut first, &mut second, 0.0);
+    assert_eq!((first.center - second.center).length(), 10.0);
+}
+
+#[test]
+fn world_two_body_collision() {
+    let mut rand = StdRng::seed_from_u64(4);
+    for _ in 0..100 {
+        // two circles heading straight at each other with no gravity or drag
+        let mut world = World::new(10000.0, 10000.0);
+        world.gravity = FVec2D::new(0.0, 0.0);
+        world.drag = 0.0;
+        let mut first = random_circle(&mut rand);
+        let mut second = random_circle(&mut rand);
+        first.center = FVec2D::new(5000.0, 5000.0);
+        let offset = FVec2D::new(rand.gen_range(-1.0..1.0), rand.gen_range(-1.0..1.0))
+            .unit_vector()
+            * (first.radius + second.radius + 1.0);
+        second.center = first.center + offset;
+        first.speed = offset.unit_vector() * rand.gen_range(50.0..200.0);
+        second.speed = -offset.unit_vector() * rand.gen_range(50.0..200.0);
+        world.circles = vec![first, second].into();
+
+        let momentum_before = momentum(&world.circles.to_vec());
+        let energy_before = kinetic_energy(&world.circles.to_vec());
+        world.step(1.0 / 30.0);
+
+        let circles = &world.circles.to_vec();
+        let scale = (first.mass + second.mass) * 300.0;
+        let momentum_after = momentum(circles);
+        assert_close(momentum_before.x, momentum_after.x, scale);
+        assert_close(momentum_before.y, momentum_after.y, scale);
+        assert_close(energy_before, kinetic_energy(circles), energy_before);
+
+        // they bounced off each other and are not overlapping anymore
+        let distance = circles[1].center - circles[0].center;
+        assert!(distance.length() >= (first.radius + second.radius) * (1.0 - 1e-5));
+        assert!(FVec2D::dot(circles[1].speed - circles[0].speed, distance) >= 0.0);
+    }
+}
+
+// The four horizontal edges built by Drawable::setup with circles resting on top
+fn resting_row() -> World {
+    let mut world = World::new(800.0, 600.0);
+    for y in [30.0, 50.0, 80.0, 120.0].iter() {
+        world.lines.push(LineSegment::new(
+            FVec2D::new(30.0, *y),
+            FVec2D::new(300.0, *y),
+            10.0,
+        ));
+    }
+    for column in 0..16 {
+        world.circles.push(Circle::new(
+            FVec2D::new(45.0 + column as f32 * 16.0, 30.0 - 10.0 - 8.0),
+            8.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+    }
+    world
+}
+
+// A bucket made of edges with circles dropped in to form a pile
+fn resting_pile() -> World {
+    let mut world = World::new(800.0, 600.0);
+    let corners = [
+        FVec2D::new(100.0, 200.0),
+        FVec2D::new(100.0, 400.0),
+        FVec2D::new(250.0, 400.0),
+        FVec2D::new(250.0, 200.0),
+    ];
+    for side in corners.windows(2) {
+        world.lines.push(LineSegment::new(side[0], side[1], 10.0));
+    }
+    let mut rand = StdRng::seed_from_u64(6);
+    for _ in 0..40 {
+        world.circles.push(Circle::new(
+            FVec2D::new(rand.gen_range(120.0..230.0), rand.gen_range(100.0..380.0)),
+            8.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+    }
+    world
+}
+
+// Let the world settle and then return how far any circle moved in a frame
+// and whether the energy went up while resting
+fn settle(world: &mut World) -> (f32, f32) {
+    for _ in 0..600 {
+        world.step(1.0 / 60.0);
+    }
+    let settled_energy = world.diagnostics.total_energy();
+    let mut max_movement: f32 = 0.0;
+    for _ in 0..120 {
+        let before: Vec<FVec2D> = world.circles.iter().map(|c| c.center).collect();
+        world.step(1.0 / 60.0);
+        for (circle, center) in world.circles.iter().zip(before.iter()) {
+            max_movement = max_movement.max((circle.center - *center).length());
+        }
+    }
+    (
+        max_movement,
+        world.diagnostics.total_energy() - settled_energy,
+    )
+}
+
+#[test]
+fn resting_contacts_stay_still() {
+    let (movement, energy_gained) = settle(&mut resting_row());
+    assert!(movement < 0.01, "circles moved {} pixels", movement);
+    assert!(energy_gained <= 1.0, "gained {} energy", energy_gained);
+
+    let (movement, energy_gained) = settle(&mut resting_pile());
+    assert!(movement < 0.05, "circles moved {} pixels", movement);
+    assert!(energy_gained <= 1.0, "gained {} energy", energy_gained);
+}
+
+#[test]
+fn contact_response_does_not_create_energy() {
+    let mut rand = StdRng::seed_from_u64(5);
+    for _ in 0..CASES {
+        let (first, mut second) = random_circle_pair_nearby(&mut rand);
+        second.speed *= rand.gen_range(0.0..0.1);
+        let before = kinetic_energy(&[first, second]);
+        let response = contact_response(&first, &second, 1.0 / 240.0, 50.0);
+        if let Response::Resting(speed1, speed2) | Response::Bounce(speed1, speed2) = response {
+            let mut after = [first, second];
+            after[0].speed = speed1;
+            after[1].speed = speed2;
+            assert!(kinetic_energy(&after) <= before * (1.0 + 1e-4) + 1e-3);
+            let momentum_before = momentum(&[first, second]);
+            let momentum_after = momentum(&after);
+            let scale = (first.mass + second.mass) * 300.0;
+            assert_close(momentum_before.x, momentum_after.x, scale);
+            assert_close(momentum_before.y, momentum_after.y, scale);
+        }
+    }
+}
+
+// Two circles that may or may not touch, small speeds so contacts are resting or speculative
+fn random_circle_pair_nearby(rand: &mut StdRng) -> (Circle, Circle) {
+    let mut first = random_circle(rand);
+    let mut second = random_circle(rand);
+    let direction = FVec2D::new(rand.gen_range(-1.0..1.0), rand.gen_range(-1.0..1.0)).unit_vector();
+    second.center =
+        first.center + direction * (first.radius + second.radius + rand.gen_range(-1.0..1.0));
+    first.speed *= 0.05;
+    second.speed *= 0.05;
+    (first, second)
+}
+
+// Many circles dropped into a box, enough to be split across threads
+fn crowded_box(count: usize, seed: u64) -> World {
+    let mut world = World::new(1000.0, 1000.0);
+    let corners = [
+        FVec2D::new(50.0, 50.0),
+        FVec2D::new(50.0, 950.0),
+        FVec2D::new(950.0, 950.0),
+        FVec2D::new(950.0, 50.0),
+    ];
+    for side in corners.windows(2) {
+        world.lines.push(LineSegment::new(side[0], side[1], 5.0));
+    }
+    let mut rand = StdRng::seed_from_u64(seed);
+    for _ in 0..count {
+        world.circles.push(Circle::new(
+            FVec2D::new(rand.gen_range(70.0..930.0), rand.gen_range(70.0..930.0)),
+            rand.gen_range(2.0..6.0),
+            FVec2D::new(rand.gen_range(-50.0..50.0), rand.gen_range(-50.0..50.0)),
+            color::RED,
+        ));
+    }
+    world
+}
+
+#[test]
+fn grid_finds_every_pair_once() {
+    let mut rand = StdRng::seed_from_u64(7);
+    let bounds: Vec<Aabb> = (0..300)
+        .map(|_| {
+            let circle = random_circle(&mut rand);
+            Aabb::from_circle(&circle).expanded(rand.gen_range(0.0..20.0))
+        })
+        .collect();
+    let grid = Grid::new(&bounds, 16.0);
+    for i in 0..bounds.len() {
+        let mut found = Vec::new();
+        grid.candidates(i, &bounds, |j| found.push(j));
+        found.sort_unstable();
+        let expected: Vec<usize> = ((i + 1)..bounds.len())
+            .filter(|&j| bounds[i].overlaps(&bounds[j]))
+            .collect();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn batches_never_share_a_circle() {
+    let mut rand = StdRng::seed_from_u64(8);
+    let circle = random_circle(&mut rand);
+    let contacts: Vec<Contact> = (0..2000)
+        .map(|k| {
+            let first = rand.gen_range(0..50);
+            if k % 5 == 0 {
+                Contact::Edge(first, circle)
+            } else {
+                Contact::Circles(first, (first + rand.gen_range(1..50)) % 50)
+            }
+        })
+        .collect();
+    let batches = batch_contacts(&contacts, 50);
+    let mut seen = vec![false; contacts.len()];
+    for batch in batches.independent.iter() {
+        let mut used = [false; 50];
+        for &k in batch {
+            let (first, second) = contacts[k].circles();
+            for i in std::iter::once(first).chain(second) {
+                assert!(!used[i]);
+                used[i] = true;
+            }
+            seen[k] = true;
+        }
+    }
+    for &k in batches.sequential.iter() {
+        seen[k] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+}
+
+#[test]
+fn stepping_is_deterministic_across_threads() {
+    let mut single = crowded_box(1500, 9);
+    single.threads = 1;
+    let mut threaded = crowded_box(1500, 9);
+    threaded.threads = 4;
+    for _ in 0..3 {
+        single.step(1.0 / 60.0);
+        threaded.step(1.0 / 60.0);
+    }
+    assert!(single.circles == threaded.circles);
+}
+
+#[test]
+fn bodies_round_trip_circles() {
+    let mut rand = StdRng::seed_from_u64(10);
+    let circles: Vec<Circle> = (0..20).map(|_| random_circle(&mut rand)).collect();
+    let mut bodies = Bodies::from(circles.clone());
+    assert_eq!(bodies.len(), circles.len());
+    for (body, circle) in bodies.iter().zip(circles.iter()) {
+        assert_eq!(body.center, circle.center);
+        assert_eq!(body.speed, circle.speed);
+        assert_close(circle.mass, body.mass, circle.mass);
+    }
+
+    // writes through the view land in the arrays
+    let mut view = bodies.circle_mut(3);
+    *view.center = FVec2D::new(1.0, 2.0);
+    view.set_mass(4.0);
+    assert_eq!(bodies.positions()[3], FVec2D::new(1.0, 2.0));
+    assert_eq!(bodies.inverse_masses()[3], 0.25);
+    assert_eq!(bodies.get(3).mass, 4.0);
+
+    bodies.set(5, circles[0]);
+    assert_eq!(bodies.get(5).center, circles[0].center);
+    assert_eq!(bodies.radii()[5], circles[0].radius);
+}
+
+#[test]
+fn bodies_bounds_cover_travel() {
+    let mut rand = StdRng::seed_from_u64(11);
+    let circles: Vec<Circle> = (0..50).map(|_| random_circle(&mut rand)).collect();
+    let bodies = Bodies::from(circles.clone());
+    let delta_t = 1.0 / 60.0;
+    for (aabb, circle) in bodies.bounds(delta_t).iter().zip(circles.iter()) {
+        assert!(aabb.contains(circle.center + circle.speed * delta_t));
+        assert!(aabb.contains(circle.center - FVec2D::new(circle.radius, circle.radius)));
+    }
+}
+
+const INTEGRATORS: [Integrator; 3] = [
+    Integrator::SemiImplicitEuler,
+    Integrator::VelocityVerlet,
+    Integrator::PositionVerlet,
+];
+
+// Largest relative energy error of a body on a circular orbit around the origin over ten orbits
+fn orbit_energy_drift(integrator: Integrator) -> f32 {
+    // gravitational parameter and radius of 1 give a speed of 1 and a period of 2 pi
+    let gravity = |position: FVec2D, _| -position / position.length().powi(3);
+    let energy = |position: FVec2D, velocity: FVec2D| {
+        0.5 * velocity.squared_length() - 1.0 / position.length()
+    };
+    let mut position = FVec2D::new(1.0, 0.0);
+    let mut velocity = FVec2D::new(0.0, 1.0);
+    let start = energy(position, velocity);
+    let mut drift: f32 = 0.0;
+    for _ in 0..(10.0 * std::f32::consts::TAU / 0.01) as usize {
+        integrator.advance(&mut position, &mut velocity, 0.01, gravity);
+        drift = drift.max(((energy(position, velocity) - start) / start).abs());
+    }
+    drift
+}
+
+// Largest relative energy error of a ball bouncing on a springy floor over twenty seconds.
+// The floor pushes back proportionally to how deep the ball sinks into it.
+fn bouncing_ball_energy_drift(integrator: Integrator) -> f32 {
+    const GRAVITY: f32 = 100.0;
+    const STIFFNESS: f32 = 1e4;
+    let force =
+        |position: FVec2D, _| FVec2D::new(0.0, -GRAVITY + STIFFNESS * (-position.y).max(0.0));
+    let energy = |position: FVec2D, velocity: FVec2D| {
+        let depth = (-position.y).max(0.0);
+        0.5 * velocity.squared_length() + GRAVITY * position.y + 0.5 * STIFFNESS * depth * depth
+    };
+    let mut position = FVec2D::new(0.0, 100.0);
+    let mut velocity = FVec2D::new(0.0, 0.0);
+    let start = energy(position, velocity);
+    let mut drift: f32 = 0.0;
+    for _ in 0..20 * 240 {
+        integrator.advance(&mut position, &mut velocity, 1.0 / 240.0, force);
+        drift = drift.max(((energy(position, velocity) - start) / start).abs());
+    }
+    drift
+}
+
+#[test]
+fn verlet_drifts_less_on_an_orbit() {
+    let drift: Vec<f32> = INTEGRATORS.iter().map(|&i| orbit_energy_drift(i)).collect();
+    assert!(drift[0] < 1e-3, "euler drifted {}", drift[0]);
+    assert!(drift[1] < 2e-5, "velocity verlet drifted {}", drift[1]);
+    assert!(drift[2] < 2e-5, "position verlet drifted {}", drift[2]);
+}
+
+#[test]
+fn verlet_drifts_less_on_a_bouncing_ball() {
+    let drift: Vec<f32> = INTEGRATORS
+        .iter()
+        .map(|&i| bouncing_ball_energy_drift(i))
+        .collect();
+    assert!(drift[0] < 0.5, "euler drifted {}", drift[0]);
+    assert!(
+        drift[1] < drift[0] / 2.0,
+        "velocity verlet drifted {}",
+        drift[1]
+    );
+    assert!(
+        drift[2] < drift[0] / 2.0,
+        "position verlet drifted {}",
+        drift[2]
+    );
+}
+
+#[test]
+fn world_uses_selected_integrator() {
+    // velocity verlet is exact under constant gravity, euler runs ahead
+    let fall = |integrator| {
+        let mut world = World::new(1000.0, 1000.0);
+        world.drag = 0.0;
+        world.integrator = integrator;
+        world.circles.push(Circle::new(
+            FVec2D::new(500.0, 100.0),
+            5.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+        world.circles.get(0).center.y
+    };
+    let expected = 100.0 + 0.5 * 100.0;
+    assert!((fall(Integrator::VelocityVerlet) - expected).abs() < 1e-2);
+    assert!((fall(Integrator::SemiImplicitEuler) - expected).abs() > 0.1);
+}
+
+// A horizontal chain of circles pinned by its first link, left to swing down and hang
+fn hanging_chain(links: usize, compliance: f32) -> World {
+    let mut world = World::new(1000.0, 1000.0);
+    world.solver = Solver::Xpbd;
+    for k in 0..links {
+        world.circles.push(Circle::new(
+            FVec2D::new(500.0 + k as f32 * 10.0, 100.0),
+            4.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+        if k > 0 {
+            world
+                .constraints
+                .push(DistanceConstraint::new(k - 1, k, 10.0, compliance));
+        }
+    }
+    *world.circles.circle_mut(0).inverse_mass = 0.0;
+    for _ in 0..300 {
+        world.step(1.0 / 60.0);
+    }
+    world
+}
+
+#[test]
+fn xpbd_chain_keeps_its_length() {
+    let world = hanging_chain(20, 0.0);
+    let positions = world.circles.positions();
+    assert_eq!(positions[0], FVec2D::new(500.0, 100.0));
+    for link in positions.windows(2) {
+        let length = (link[1] - link[0]).length();
+        assert!((length - 10.0).abs() < 0.1, "link is {} long", length);
+    }
+    // hanging down below the pin
+    assert!(positions[19].y > 250.0);
+}
+
+#[test]
+fn xpbd_compliance_stretches_links() {
+    let rigid = hanging_chain(20, 0.0);
+    let soft = hanging_chain(20, 1e-4);
+    let top_link =
+        |world: &World| (world.circles.positions()[1] - world.circles.positions()[0]).length();
+    assert!(top_link(&soft) > top_link(&rigid) + 1.0);
+    assert!(soft.circles.positions()[19].y > rigid.circles.positions()[19].y + 5.0);
+}
+
+#[test]
+fn xpbd_pile_has_no_overlap() {
+    let mut world = resting_pile();
+    world.solver = Solver::Xpbd;
+    for _ in 0..300 {
+        world.step(1.0 / 60.0);
+    }
+    let circles = world.circles.to_vec();
+    for (i, first) in circles.iter().enumerate() {
+        for edge in world.lines.iter() {
+            let overlap = first.radius + edge.radius
+                - (first.center - edge.closest_point(first.center)).length();
+            assert!(overlap < 0.5, "circle sinks {} into an edge", overlap);
+        }
+        for second in circles.iter().skip(i + 1) {
+            let overlap = first.radius + second.radius - (first.center - second.center).length();
+            assert!(overlap < 0.5, "circles overlap by {}", overlap);
+        }
+    }
+}
+
+// A weight hanging from a pinned circle by a link that snaps above break_force
+fn hanging_weight(break_force: f32) -> World {
+    let mut world = World::new(1000.0, 1000.0);
+    world.solver = Solver::Xpbd;
+    world.drag = 0.0;
+    for k in 0..2 {
+        world.circles.push(Circle::new(
+            FVec2D::new(500.0, 100.0 + k as f32 * 10.0),
+            4.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+    }
+    *world.circles.circle_mut(0).inverse_mass = 0.0;
+    world
+        .constraints
+        .push(DistanceConstraint::new(0, 1, 10.0, 0.0).with_break_force(break_force));
+    world
+}
+
+#[test]
+fn links_snap_above_their_break_force() {
+    // the weight pulls with its mass of 40 times gravity
+    let mut strong = hanging_weight(6000.0);
+    let mut weak = hanging_weight(3000.0);
+    for _ in 0..60 {
+        strong.step(1.0 / 60.0);
+        assert!(strong.events.is_empty());
+    }
+    assert_eq!(strong.constraints.len(), 1);
+
+    weak.step(1.0 / 60.0);
+    assert!(weak.constraints.is_empty());
+    match weak.events.as_slice() {
+        [Event::JointBroken { joint, force }] => {
+            assert_eq!((joint.first, joint.second), (0, 1));
+            assert!((force - 4000.0).abs() < 100.0, "broke at {}", force);
+        }
+        events => panic!("expected one broken joint, got {:?}", events),
+    }
+    for _ in 0..60 {
+        weak.step(1.0 / 60.0);
+    }
+    assert!(weak.circles.positions()[1].y > 150.0);
+}
+
+// A 3 by 3 block of welded circles thrown down at a floor, returns the world after it landed
+fn thrown_cluster(speed: f32) -> World {
+    let mut world = World::new(800.0, 600.0);
+    world.solver = Solver::Xpbd;
+    world.lines.push(LineSegment::new(
+        FVec2D::new(0.0, 500.0),
+        FVec2D::new(800.0, 500.0),
+        5.0,
+    ));
+    let mut circles = Vec::new();
+    for row in 0..3 {
+        for column in 0..3 {
+            circles.push(Circle::new(
+                FVec2D::new(400.0 + column as f32 * 8.0, 460.0 + row as f32 * 8.0),
+                4.0,
+                FVec2D::new(0.0, speed),
+                color::RED,
+            ));
+        }
+    }
+    world.add_cluster(circles, 50000.0);
+    for _ in 0..60 {
+        world.step(1.0 / 60.0);
+        if !world.events.is_empty() {
+            break;
+        }
+    }
+    world
+}
+
+#[test]
+fn clusters_shatter_on_hard_impacts() {
+    let gentle = thrown_cluster(20.0);
+    assert!(gentle.events.is_empty());
+    assert_eq!(gentle.clusters.len(), 1);
+    let positions = gentle.circles.positions();
+    for weld in gentle.clusters[0].welds.iter() {
+        let length = (positions[weld.first] - positions[weld.second]).length();
+        assert!((length - weld.rest_length).abs() < 0.1);
+    }
+
+    let hard = thrown_cluster(400.0);
+    assert!(hard.clusters.is_empty());
+    match hard.events.as_slice() {
+        [Event::ClusterShattered { members, impulse }] => {
+            assert_eq!(members.len(), 9);
+            assert!(*impulse > 50000.0);
+        }
+        events => panic!("expected one shattered cluster, got {:?}", events),
+    }
+}
+
+// A circle hanging 40 to the right of a pinned circle on a pin, let go to swing down
+fn pendulum(limits: Option<(f32, f32)>) -> World {
+    let mut world = World::new(1000.0, 1000.0);
+    world.solver = Solver::Xpbd;
+    world.drag = 0.0;
+    let pivot = FVec2D::new(500.0, 100.0);
+    world
+        .circles
+        .push(Circle::new(pivot, 5.0, FVec2D::new(0.0, 0.0), color::RED));
+    world.circles.push(Circle::new(
+        pivot + FVec2D::new(40.0, 0.0),
+        5.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    ));
+    *world.circles.circle_mut(0).inverse_mass = 0.0;
+    let mut joint = RevoluteJoint::new(&world.circles, 0, 1, pivot);
+    if let Some((lower, upper)) = limits {
+        joint = joint.with_limits(lower, upper);
+    }
+    world.joints.push(joint);
+    world
+}
+
+// Angle of the arm from the pivot to the bob, clockwise on screen from pointing right
+fn arm_angle(world: &World) -> f32 {
+    let arm = world.circles.positions()[1] - world.circles.positions()[0];
+    arm.y.atan2(arm.x)
+}
+
+#[test]
+fn revolute_joint_swings_around_its_pin() {
+    let mut world = pendulum(None);
+    let mut lowest = 0.0;
+    for _ in 0..120 {
+        world.step(1.0 / 60.0);
+        let (pin1, pin2) = world.joints[0].pins(&world.circles);
+        assert!((pin1 - pin2).length() < 0.1);
+        let arm = world.circles.positions()[1] - world.circles.positions()[0];
+        assert!((arm.length() - 40.0).abs() < 0.1);
+        // the bob turns with the arm it hangs from
+        assert!((world.circles.angles()[1] - arm_angle(&world)).abs() < 0.01);
+        lowest = f32::max(lowest, arm_angle(&world));
+    }
+    // swings down to hang straight under the pin
+    assert!(lowest > 1.4, "only swung to {}", lowest);
+}
+
+#[test]
+fn revolute_joint_limits_hold_and_break() {
+    let mut world = pendulum(Some((-0.5, 0.5)));
+    for _ in 0..120 {
+        world.step(1.0 / 60.0);
+        assert!(
+            arm_angle(&world) < 0.52,
+            "swung past the limit to {}",
+            arm_angle(&world)
+        );
+    }
+    assert!(world.events.is_empty());
+
+    // holding the arm at the limit takes about 50 * 100 * 40 * cos(0.5)
+    let mut world = pendulum(Some((-0.5, 0.5)));
+    world.joints[0] = world.joints[0].with_break_torque(100000.0);
+    let mut broken = None;
+    for _ in 0..120 {
+        world.step(1.0 / 60.0);
+        if let Some(Event::RevoluteJointBroken { torque, .. }) = world.events.first() {
+            broken = Some(*torque);
+        }
+    }
+    assert!(world.joints.is_empty());
+    assert!(broken.is_some_and(|torque| torque > 100000.0));
+}
+
+// A wheel of radius 10 and mass 100 pinned at its center to a pinned circle, driven for a second
+fn driven_wheel(max_torque: f32) -> f32 {
+    let mut world = World::new(1000.0, 1000.0);
+    world.solver = Solver::Xpbd;
+    let center = FVec2D::new(500.0, 500.0);
+    for _ in 0..2 {
+        world
+            .circles
+            .push(Circle::new(center, 10.0, FVec2D::new(0.0, 0.0), color::RED));
+    }
+    *world.circles.circle_mut(0).inverse_mass = 0.0;
+    world
+        .joints
+        .push(RevoluteJoint::new(&world.circles, 0, 1, center).with_motor(5.0, max_torque));
+    for _ in 0..60 {
+        world.step(1.0 / 60.0);
+    }
+    world.circles.angular_speeds()[1]
+}
+
+#[test]
+fn motors_turn_up_to_their_target_speed() {
+    assert!((driven_wheel(1e6) - 5.0).abs() < 0.01);
+    // the wheel has an inertia of 5000, a torque of 5000 speeds it up by 1 per second
+    assert!((driven_wheel(5000.0) - 1.0).abs() < 0.05);
+}
+
+// Locked chassis of two circles 60 apart with a motorized wheel under each end, driven on a floor
+fn car_distance(wheel_speed: f32) -> f32 {
+    let mut world = World::new(1000.0, 600.0);
+    world.solver = Solver::Xpbd;
+    world.friction = 0.9;
+    world.drag = 0.0;
+    world.lines.push(LineSegment::new(
+        FVec2D::new(0.0, 500.0),
+        FVec2D::new(1000.0, 500.0),
+        5.0,
+    ));
+    let still = FVec2D::new(0.0, 0.0);
+    let (rear, front) = (FVec2D::new(400.0, 470.0), FVec2D::new(460.0, 470.0));
+    let axle = FVec2D::new(0.0, 10.0);
+    for &(center, radius) in [
+        (rear, 6.0),
+        (front, 6.0),
+        (rear + axle, 12.0),
+        (front + axle, 12.0),
+    ]
+    .iter()
+    {
+        world
+            .circles
+            .push(Circle::new(center, radius, still, color::RED));
+    }
+    let circles = &world.circles;
+    world.joints = vec![
+        RevoluteJoint::new(circles, 0, 1, (rear + front) / 2.0).with_limits(0.0, 0.0),
+        RevoluteJoint::new(circles, 0, 2, rear + axle).with_motor(wheel_speed, 200000.0),
+        RevoluteJoint::new(circles, 1, 3, front + axle).with_motor(wheel_speed, 200000.0),
+    ];
+    for _ in 0..120 {
+        world.step(1.0 / 60.0);
+    }
+    let chassis = world.circles.positions()[1] - world.circles.positions()[0];
+    assert!(
+        (chassis.length() - 60.0).abs() < 0.5,
+        "chassis bent to {}",
+        chassis.length()
+    );
+    world.circles.positions()[0].x - rear.x
+}
+
+#[test]
+fn motorized_car_drives_on_edges() {
+    let parked = car_distance(0.0);
+    let driven = car_distance(10.0);
+    let reversed = car_distance(-10.0);
+    assert!(parked.abs() < 2.0, "parked car rolled {}", parked);
+    // wheels of radius 12 turning at 10 cover 120 per second once the car is up to speed
+    assert!(driven > 80.0, "car only drove {}", driven);
+    assert!(reversed < -80.0, "car only reversed {}", reversed);
+}
+
+#[test]
+fn despawn_keeps_links_on_the_same_circles() {
+    let mut world = World::new(1000.0, 1000.0);
+    for k in 0..4 {
+        world.spawn(Circle::new(
+            FVec2D::new(100.0 * k as f32, 0.0),
+            5.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+    }
+    world
+        .constraints
+        .push(DistanceConstraint::new(0, 3, 300.0, 0.0));
+    world
+        .constraints
+        .push(DistanceConstraint::new(1, 2, 100.0, 0.0));
+    world.joints.push(RevoluteJoint::new(
+        &world.circles,
+        2,
+        3,
+        FVec2D::new(250.0, 0.0),
+    ));
+
+    let removed = world.despawn(1);
+    assert_eq!(removed.center, FVec2D::new(100.0, 0.0));
+    assert_eq!(world.circles.len(), 3);
+    // the last circle took index 1
+    assert_eq!(world.circles.positions()[1], FVec2D::new(300.0, 0.0));
+    assert_eq!(world.constraints.len(), 1);
+    assert_eq!(
+        (world.constraints[0].first, world.constraints[0].second),
+        (0, 1)
+    );
+    assert_eq!((world.joints[0].first, world.joints[0].second), (2, 1));
+}
+
+#[test]
+fn emitted_circles_expire() {
+    let mut world = World::new(1000.0, 1000.0);
+    world.gravity = FVec2D::new(0.0, 0.0);
+    let mut emitter = Emitter::new(FVec2D::new(500.0, 500.0), 30.0, 7);
+    emitter.lifetime = 0.5..=0.5;
+    world.emitters.push(emitter);
+    let mut despawned = 0;
+    for _ in 0..60 {
+        world.step(1.0 / 60.0);
+        for event in world.events.iter() {
+            if let Event::Despawned { .. } = event {
+                despawned += 1;
+            }
+        }
+    }
+    // 30 spawned over the second, the ones from the last half second are still around
+    assert_eq!(despawned + world.circles.len(), 30);
+    assert!((14..=16).contains(&world.circles.len()));
+    for &lifetime in world.circles.lifetimes() {
+        assert!(lifetime > 0.0 && lifetime <= 0.5);
+    }
+}
+
+#[test]
+fn emitters_accept_negative_spread_and_reversed_ranges() {
+    let mut emitter = Emitter::new(FVec2D::new(500.0, 500.0), 100.0, 3);
+    emitter.direction = 0.0;
+    emitter.spread = -0.5;
+    emitter.radius = 5.0..=2.0;
+    emitter.speed = 250.0..=150.0;
+    emitter.lifetime = 4.0..=2.0;
+    let emitted = emitter.emit(1.0);
+    assert_eq!(emitted.len(), 100);
+    for (circle, lifetime) in emitted {
+        assert!((2.0..=5.0).contains(&circle.radius));
+        assert!((149.9..=250.1).contains(&circle.speed.length()));
+        assert!(circle.speed.y.atan2(circle.speed.x).abs() <= 0.5);
+        assert!((2.0..=4.0).contains(&lifetime));
+    }
+}
+
+#[test]
+fn open_worlds_despawn_leaving_circles() {
+    let leaving = |boundary: Boundary| {
+        let mut world = World::new(100.0, 100.0);
+        world.boundary = boundary;
+        world.gravity = FVec2D::new(0.0, 0.0);
+        world.drag = 0.0;
+        world.spawn(Circle::new(
+            FVec2D::new(50.0, 50.0),
+            5.0,
+            FVec2D::new(100.0, 0.0),
+            color::RED,
+        ));
+        for _ in 0..60 {
+            world.step(1.0 / 60.0);
+        }
+        world.circles.len()
+    };
+    assert_eq!(leaving(Boundary::Wrap), 1);
+    assert_eq!(leaving(Boundary::Open), 0);
+}
+
+// Pour small circles onto a floor from a single point and return the slope angle
+// of the pile in degrees, from its height and the width of its base
+fn pour_pile(granular: Option<Granular>) -> f32 {
+    let mut world = World::new(800.0, 600.0);
+    world.granular = granular;
+    world.lines.push(LineSegment::new(
+        FVec2D::new(0.0, 500.0),
+        FVec2D::new(800.0, 500.0),
+        5.0,
+    ));
+    let mut rand = StdRng::seed_from_u64(12);
+    for step in 0..600 {
+        if step % 2 == 0 && step < 400 {
+            world.circles.push(Circle::new(
+                FVec2D::new(400.0 + rand.gen_range(-1.0..1.0), 300.0),
+                3.0,
+                FVec2D::new(0.0, 0.0),
+                color::RED,
+            ));
+        }
+        world.step(1.0 / 60.0);
+    }
+    let circles = world.circles.to_vec();
+    let top = circles.iter().map(|c| c.center.y).fold(f32::MAX, f32::min);
+    let height = 495.0 - top;
+    // half the width of the base, ignoring the few grains that rolled away
+    let mut offsets: Vec<f32> = circles
+        .iter()
+        .filter(|c| c.center.y > 488.0)
+        .map(|c| (c.center.x - 400.0).abs())
+        .collect();
+    offsets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let half_width = offsets[offsets.len() * 9 / 10];
+    (height / half_width).atan().to_degrees()
+}
+
+#[test]
+fn sand_piles_hold_their_slope() {
+    let frictionless = pour_pile(None);
+    let sand = pour_pile(Some(Granular::sand()));
+    let wet_sand = pour_pile(Some(Granular::wet_sand()));
+    assert!(
+        frictionless < 20.0,
+        "frictionless pile at {} degrees",
+        frictionless
+    );
+    assert!(sand > 20.0, "sand pile at {} degrees", sand);
+    assert!(wet_sand > sand, "wet sand pile at {} degrees", wet_sand);
+}
+
+#[test]
+fn rolling_resistance_steepens_piles() {
+    let rolling = pour_pile(Some(Granular::sand()));
+    let free = pour_pile(Some(Granular {
+        rolling_resistance: 0.0,
+        ..Granular::sand()
+    }));
+    // grains free to roll over each other spread out almost as flat as frictionless ones
+    assert!(
+        rolling > free + 5.0,
+        "pile at {} degrees with rolling resistance, {} without",
+        rolling,
+        free
+    );
+}
+
+#[test]
+fn cohesion_pulls_every_circle_in_range() {
+    let wet_sand = Granular::wet_sand();
+    let first = Circle::new(
+        FVec2D::new(0.0, 0.0),
+        3.0,
+        FVec2D::new(-1.0, 0.0),
+        color::RED,
+    );
+    // drifting apart within the cohesion range, the pull holds them back
+    let drifting = Circle::new(
+        FVec2D::new(6.5, 0.0),
+        3.0,
+        FVec2D::new(1.0, 0.0),
+        color::RED,
+    );
+    let (change1, change2) = wet_sand.pull(&first, &drifting, 0.01).unwrap();
+    assert!(change1.x > 0.0);
+    assert_eq!(change2, -change1);
+    let far = Circle::new(
+        FVec2D::new(7.5, 0.0),
+        3.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    assert_eq!(wet_sand.pull(&first, &far, 0.01), None);
+    assert_eq!(Granular::sand().pull(&first, &drifting, 0.01), None);
+
+    let wall = Circle::new(
+        FVec2D::new(6.5, 0.0),
+        3.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    assert!(wet_sand.pull_edge(&first, &wall, 0.01).unwrap().x > 0.0);
+
+    // friction leaves contacts without a response alone
+    assert_eq!(
+        wet_sand.contact(&first, &drifting, Response::None),
+        (Response::None, 0.0, 0.0)
+    );
+    assert_eq!(
+        wet_sand.edge(&first, &wall, Response::None),
+        (Response::None, 0.0)
+    );
+}
+
+#[test]
+fn cohesion_pull_does_not_depend_on_iterations() {
+    let pulled = |max_simulation_steps| {
+        let mut world = World::new(800.0, 600.0);
+        world.gravity = FVec2D::new(0.0, 0.0);
+        world.granular = Some(Granular::wet_sand());
+        world.max_simulation_steps = max_simulation_steps;
+        world.circles.push(Circle::new(
+            FVec2D::new(100.0, 100.0),
+            3.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+        world.circles.push(Circle::new(
+            FVec2D::new(106.5, 100.0),
+            3.0,
+            FVec2D::new(0.0, 0.0),
+            color::RED,
+        ));
+        // a fast circle bouncing off a wall keeps the collision iterations going
+        world.lines.push(LineSegment::new(
+            FVec2D::new(500.0, 0.0),
+            FVec2D::new(500.0, 600.0),
+            5.0,
+        ));
+        world.circles.push(Circle::new(
+            FVec2D::new(488.0, 300.0),
+            3.0,
+            FVec2D::new(600.0, 0.0),
+            color::RED,
+        ));
+        world.step(1.0 / 60.0);
+        (world.circles.to_vec()[0].speed, world.stats.iterations)
+    };
+    let (once, iterations) = pulled(1);
+    let (repeated, more_iterations) = pulled(15);
+    assert!(more_iterations > iterations);
+    assert!(once.x > 0.0);
+    assert!(
+        (repeated - once).length() < 1e-4,
+        "{:?} after {} iterations, {:?} after {}",
+        repeated,
+        more_iterations,
+        once,
+        iterations
+    );
+}
+
+#[test]
+fn friction_sets_grains_rolling() {
+    let sand = Granular {
+        rolling_resistance: 0.0,
+        ..Granular::sand()
+    };
+    // a circle sliding along a wall below it while pressed onto it
+    let circle = Circle::new(
+        FVec2D::new(0.0, 0.0),
+        3.0,
+        FVec2D::new(10.0, 0.0),
+        color::RED,
+    );
+    let wall = Circle::new(
+        FVec2D::new(0.0, 5.0),
+        2.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    let pressed = Response::Resting(FVec2D::new(10.0, -5.0), FVec2D::new(0.0, 0.0));
+    let (response, spin) = sand.edge(&circle, &wall, pressed);
+    match response {
+        Response::Resting(speed, _) => assert!(speed.x < 10.0),
+        response => panic!("{:?}", response),
+    }
+    // turning clockwise on screen, the way a ball rolls to the right
+    assert!(spin > 0.0);
+}
+
+// Tank 100 wide with its floor at y = 500, filled with water up to y = 420
+fn water_tank() -> World {
+    let mut world = World::new(800.0, 600.0);
+    world.lines = vec![
+        LineSegment::new(FVec2D::new(300.0, 500.0), FVec2D::new(400.0, 500.0), 2.0),
+        LineSegment::new(FVec2D::new(300.0, 300.0), FVec2D::new(300.0, 500.0), 2.0),
+        LineSegment::new(FVec2D::new(400.0, 300.0), FVec2D::new(400.0, 500.0), 2.0),
+    ];
+    world
+        .fluid
+        .fill(FVec2D::new(306.0, 420.0), FVec2D::new(394.0, 494.0));
+    world
+}
+
+#[test]
+fn fluid_settles_in_a_tank() {
+    let mut world = water_tank();
+    for _ in 0..180 {
+        world.step(1.0 / 60.0);
+    }
+    let fluid = &world.fluid;
+    for position in fluid.positions() {
+        assert!(
+            position.x > 300.0 && position.x < 400.0 && position.y < 500.0,
+            "particle leaked out of the tank at {:?}",
+            position
+        );
+    }
+    let average = fluid.densities().iter().sum::<f32>() / fluid.len() as f32;
+    assert!(
+        average > 0.7 && average < 1.3,
+        "average density {} far from rest density",
+        average
+    );
+    let fastest = fluid
+        .velocities()
+        .iter()
+        .map(|v| v.length())
+        .fold(0.0, f32::max);
+    assert!(fastest < 30.0, "fluid still sloshing at {}", fastest);
+}
+
+// Height of a circle of the given mass dropped into the tank and of the water surface
+fn circle_depth_after_dropping(mass: f32) -> (f32, f32) {
+    let mut world = water_tank();
+    let mut circle = Circle::new(
+        FVec2D::new(350.0, 440.0),
+        8.0,
+        FVec2D::new(0.0, 0.0),
+        color::RED,
+    );
+    circle.mass = mass;
+    world.circles.push(circle);
+    for _ in 0..180 {
+        world.step(1.0 / 60.0);
+    }
+    let surface = world
+        .fluid
+        .positions()
+        .iter()
+        .map(|p| p.y)
+        .fold(f32::MAX, f32::min);
+    (world.circles.get(0).center.y, surface)
+}
+
+#[test]
+fn light_circles_float_and_heavy_ones_sink() {
+    // half as dense as the water and five times denser
+    let (light, surface) = circle_depth_after_dropping(80.0);
+    let (heavy, _) = circle_depth_after_dropping(1000.0);
+    assert!(
+        light < surface + 4.0,
+        "light circle did not float, at y = {} under a surface at {}",
+        light,
+        surface
+    );
+    assert!(heavy > 485.0, "heavy circle did not sink, at y = {}", heavy);
+}
@@ -0,0 +1,612 @@
+use crate::balls::{Circle, LineSegment};
+use crate::math::vector::FVec2D;
+use crate::physics::bodies::Bodies;
+use crate::physics::broadphase::Grid;
+use crate::physics::cluster::Cluster;
+use crate::physics::collision::{
+    batch_contacts, contact_response, edge_response, separate_circles, Batches, Contact, Response,
+};
+use crate::physics::diagnostics::{Diagnostics, DiagnosticsHistory};
+use crate::physics::emitter::Emitter;
+use crate::physics::fluid;
+use crate::physics::fluid::Fluid;
+use crate::physics::granular::Granular;
+use crate::physics::integrator::Integrator;
+use crate::physics::joint::RevoluteJoint;
+use crate::physics::parallel;
+use crate::physics::query::Aabb;
+use crate::physics::xpbd;
+use crate::physics::xpbd::DistanceConstraint;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// The physics world: every circle and static edge in the scene and the settings used to move them.
+/// It knows nothing about windows or drawing so it can be stepped headlessly.
+///
+/// Circles leaving the world on one side wrap around to the other side, or are despawned
+/// once they are outside an open world.
+///
+/// Circles are kept in Bodies, one array per property, see Bodies for how to read and change them.
+///
+pub struct World {
+    pub width: f32,
+    pub height: f32,
+    pub circles: Bodies,
+    pub lines: Vec<LineSegment>,
+    /// Liquid particles flowing around the circles and edges
+    pub fluid: Fluid,
+    /// Links between circles, only enforced by the XPBD solver
+    pub constraints: Vec<DistanceConstraint>,
+    /// Circles welded together until hit hard enough, only held together by the XPBD solver
+    pub clusters: Vec<Cluster>,
+    /// Pins between circles, only enforced by the XPBD solver
+    pub joints: Vec<RevoluteJoint>,
+    /// Sources spawning circles every step
+    pub emitters: Vec<Emitter>,
+    /// What happens to bodies leaving the world
+    pub boundary: Boundary,
+    /// How contacts and constraints are resolved
+    pub solver: Solver,
+    /// Constant acceleration applied to every circle
+    pub gravity: FVec2D,
+    /// Drag coefficient, slows circles down proportionally to their speed
+    pub drag: f32,
+    /// How circles are moved under gravity and drag
+    pub integrator: Integrator,
+    /// Overlap allowed between bodies in contact before they get pushed apart
+    pub contact_slop: f32,
+    /// Contacts approaching slower than this come to rest instead of bouncing
+    pub resting_speed: f32,
+    /// Friction and cohesion between touching circles, None for frictionless circles.
+    /// Only used by the impulse solver
+    pub granular: Option<Granular>,
+    /// Coulomb friction coefficient between touching bodies, lets circles roll and wheels grip.
+    /// Only used by the XPBD solver
+    pub friction: f32,
+    /// Number of sub steps each step is split into
+    pub simulation_updates: u32,
+    /// Maximum number of collision iterations per sub step, the XPBD solver always runs all of them
+    pub max_simulation_steps: u32,
+    /// Number of threads used to step the world, 1 keeps everything on the calling thread
+    pub threads: usize,
+    /// Total simulated time in seconds
+    pub time: f32,
+    /// Energy and momentum measured at the end of the last step
+    pub diagnostics: Diagnostics,
+    /// Diagnostics of the last few steps
+    pub history: DiagnosticsHistory,
+    /// How much work the last step took
+    pub stats: StepStats,
+    /// Joints that broke and clusters that shattered during the last step
+    pub events: Vec<Event>,
+}
+
+/// Ways of resolving contacts, a scene can switch between them at any time
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Solver {
+    /// Circles are pushed apart and bounce off each other by changing their speeds
+    #[default]
+    Impulse,
+    /// Circles are moved until nothing overlaps and their speeds follow from how far they moved,
+    /// see xpbd::step. Distance constraints are only enforced by this solver
+    Xpbd,
+}
+
+/// Something that happened during a step, see World::events
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// A link pulled or pushed harder than its break force and was removed from World::constraints
+    JointBroken {
+        joint: DistanceConstraint,
+        force: f32,
+    },
+    /// A cluster was hit harder than its shatter impulse and was removed from World::clusters,
+    /// its members now move on their own
+    ClusterShattered { members: Vec<usize>, impulse: f32 },
+    /// A circle ran out of lifetime or left an open world and was despawned,
+    /// the circle that had index replaced_by now has its index
+    Despawned {
+        circle: Circle,
+        index: usize,
+        replaced_by: Option<usize>,
+    },
+    /// A pin took more than its break force or torque and was removed from World::joints
+    RevoluteJointBroken {
+        joint: RevoluteJoint,
+        force: f32,
+        torque: f32,
+    },
+}
+
+/// What happens to bodies leaving the world
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Boundary {
+    /// They come back on the other side
+    #[default]
+    Wrap,
+    /// They are despawned once they are completely outside
+    Open,
+}
+
+/// Work done during one step, summed over all its sub steps and iterations
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct StepStats {
+    /// Collision iterations run
+    pub iterations: u32,
+    /// Pairs of bodies the broadphase handed to the narrowphase
+    pub pairs_tested: usize,
+    /// Contacts found by the narrowphase
+    pub contacts: usize,
+}
+
+impl World {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            circles: Bodies::new(),
+            lines: Vec::new(),
+            fluid: Fluid::new(),
+            constraints: Vec::new(),
+            clusters: Vec::new(),
+            joints: Vec::new(),
+            emitters: Vec::new(),
+            boundary: Boundary::default(),
+            solver: Solver::default(),
+            gravity: FVec2D::new(0.0, 100.0),
+            drag: 0.8,
+            integrator: Integrator::default(),
+            contact_slop: 0.5,
+            resting_speed: 50.0,
+            granular: None,
+            friction: 0.0,
+            simulation_updates: 4,
+            max_simulation_steps: 15,
+            threads: parallel::available_threads(),
+            time: 0.0,
+            diagnostics: Diagnostics::default(),
+            history: DiagnosticsHistory::new(600),
+            stats: StepStats::default(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Add a circle to the world and return its index
+    pub fn spawn(&mut self, circle: Circle) -> usize {
+        self.circles.push(circle)
+    }
+
+    /// Add a circle that is despawned after lifetime seconds and return its index
+    pub fn spawn_for(&mut self, circle: Circle, lifetime: f32) -> usize {
+        let i = self.circles.push(circle);
+        self.circles.set_lifetime(i, lifetime);
+        i
+    }
+
+    ///
+    /// Remove circle i from the world and return it.
+    /// The last circle takes over index i. Links, joints and clusters are updated to match,
+    /// the links and joints holding circle i are removed with it.
+    ///
+    pub fn despawn(&mut self, i: usize) -> Circle {
+        let last = self.circles.len() - 1;
+        let circle = self.circles.swap_remove(i);
+        let moved = |k: usize| if k == last { i } else { k };
+
+        self.constraints
+            .retain(|link| link.first != i && link.second != i);
+        for link in self.constraints.iter_mut() {
+            link.first = moved(link.first);
+            link.second = moved(link.second);
+        }
+        self.joints
+            .retain(|joint| joint.first != i && joint.second != i);
+        for joint in self.joints.iter_mut() {
+            joint.first = moved(joint.first);
+            joint.second = moved(joint.second);
+        }
+        for cluster in self.clusters.iter_mut() {
+            cluster.members.retain(|&member| member != i);
+            cluster
+                .welds
+                .retain(|weld| weld.first != i && weld.second != i);
+            for member in cluster.members.iter_mut() {
+                *member = moved(*member);
+            }
+            for weld in cluster.welds.iter_mut() {
+                weld.first = moved(weld.first);
+                weld.second = moved(weld.second);
+            }
+        }
+        self.clusters.retain(|cluster| !cluster.members.is_empty());
+        circle
+    }
+
+    ///
+    /// Add circles welded together into a cluster that shatters when an impact delivers
+    /// more than shatter_impulse, see Cluster::weld.
+    /// Returns the index of the cluster
+    ///
+    pub fn add_cluster(&mut self, circles: Vec<Circle>, shatter_impulse: f32) -> usize {
+        let members = circles
+            .into_iter()
+            .map(|circle| self.circles.push(circle))
+            .collect();
+        self.clusters
+            .push(Cluster::weld(&self.circles, members, shatter_impulse));
+        self.clusters.len() - 1
+    }
+
+    /// Energy and momentum of the world as it is right now
+    pub fn measure(&self) -> Diagnostics {
+        Diagnostics::measure(
+            self.time,
+            &self.circles,
+            self.gravity,
+            FVec2D::new(0.0, self.height),
+            FVec2D::new(self.width / 2.0, self.height / 2.0),
+        )
+    }
+
+    ///
+    /// Advance the simulation by delta_t seconds
+    /// Circles are moved, collisions with edges and other circles are resolved, the fluid
+    /// flows and pushes circles around and the diagnostics for the step are recorded.
+    ///
+    /// Each iteration runs in phases: move the circles, find contacts, push overlapping circles
+    /// apart and update their speeds. Moving circles and finding contacts is split across
+    /// threads, contacts are solved in batches that never share a circle. The result is the same
+    /// no matter how many threads are used.
+    ///
+    pub fn step(&mut self, delta_t: f32) {
+        self.stats = StepStats::default();
+        self.events.clear();
+        self.emit(delta_t);
+        match self.solver {
+            Solver::Impulse => self.step_impulse(delta_t),
+            Solver::Xpbd => xpbd::step(self, delta_t),
+        }
+        if !self.fluid.is_empty() {
+            fluid::step(self, delta_t);
+        }
+        self.expire(delta_t);
+
+        self.time += delta_t;
+        self.diagnostics = self.measure();
+        self.history.push(self.diagnostics);
+    }
+
+    // Spawn the circles every emitter owes for the step
+    fn emit(&mut self, delta_t: f32) {
+        for emitter in self.emitters.iter_mut() {
+            for (circle, lifetime) in emitter.emit(delta_t) {
+                let i = self.circles.push(circle);
+                self.circles.set_lifetime(i, lifetime);
+            }
+        }
+    }
+
+    // Age every circle and despawn the ones that ran out of lifetime or left an open world.
+    // Despawning from the highest index down leaves the indices still to go untouched
+    fn expire(&mut self, delta_t: f32) {
+        let open = self.boundary == Boundary::Open;
+        let (width, height) = (self.width, self.height);
+        let mut expired = Vec::new();
+        let circles = &mut self.circles;
+        for i in 0..circles.len() {
+            circles.lifetimes[i] -= delta_t;
+            let (position, radius) = (circles.positions[i], circles.radii[i]);
+            let outside = position.x < -radius
+                || position.y < -radius
+                || position.x > width + radius
+                || position.y > height + radius;
+            if circles.lifetimes[i] <= 0.0 || (open && outside) {
+                expired.push(i);
+            }
+        }
+        for &i in expired.iter().rev() {
+            let last = self.circles.len() - 1;
+            let circle = self.despawn(i);
+            self.events.push(Event::Despawned {
+                circle,
+                index: i,
+                replaced_by: if last != i { Some(last) } else { None },
+            });
+        }
+    }
+
+    // Step with the impulse solver
+    fn step_impulse(&mut self, delta_t: f32) {
+        let sim_elapsed_time = delta_t / self.simulation_updates as f32;
+        for _ in 0..self.simulation_updates {
+            for time_remaining in self.circles.time_remaining.iter_mut() {
+                *time_remaining = sim_elapsed_time;
+            }
+            for _ in 0..self.max_simulation_steps {
+                // nothing left to simulate in this sub step, rounding errors aside
+                let threshold = sim_elapsed_time * 1e-3;
+                if !self.circles.time_remaining.iter().any(|&t| t > threshold) {
+                    break;
+                }
+                let travelled = self.integrate();
+                let (contacts, pairs_tested) = self.find_contacts(sim_elapsed_time);
+                self.stats.iterations += 1;
+                self.stats.pairs_tested += pairs_tested;
+                self.stats.contacts += contacts.len();
+                let batches = batch_contacts(&contacts, self.circles.len());
+                self.separate(&contacts, &batches);
+                self.consume_time(&travelled);
+                self.respond(&contacts, &batches, sim_elapsed_time);
+            }
+            self.cohere(sim_elapsed_time);
+            // circles turn at their own speed, contacts only change it in granular mode
+            let circles = &mut self.circles;
+            for (angle, &angular_speed) in
+                circles.angles.iter_mut().zip(circles.angular_speeds.iter())
+            {
+                *angle += angular_speed * sim_elapsed_time;
+            }
+        }
+    }
+
+    // Pull circles within the cohesion range of each other or of an edge together over delta_t.
+    // Done once per sub step, however many iterations it took to use up its time
+    fn cohere(&mut self, delta_t: f32) {
+        let granular = match self.granular {
+            Some(granular) if granular.cohesion != 0.0 => granular,
+            _ => return,
+        };
+        let (contacts, _) = self.find_contacts(delta_t);
+        let circles = &self.circles;
+        // every pull is worked out from the speeds before any of them is applied
+        let pulls = parallel::flat_map(
+            &contacts,
+            self.threads,
+            |_, contact, pulls| match *contact {
+                Contact::Circles(i, j) => {
+                    let (first, second) = (circles.contact_circle(i), circles.contact_circle(j));
+                    if let Some((change1, change2)) = granular.pull(&first, &second, delta_t) {
+                        pulls.push((i, change1));
+                        pulls.push((j, change2));
+                    }
+                }
+                Contact::Edge(i, fake_circle) => {
+                    let circle = circles.contact_circle(i);
+                    if let Some(change) = granular.pull_edge(&circle, &fake_circle, delta_t) {
+                        pulls.push((i, change));
+                    }
+                }
+            },
+        );
+        for (i, change) in pulls {
+            self.circles.velocities[i] += change;
+        }
+    }
+
+    // Move every circle with time left by its speed after applying gravity and drag.
+    // Returns how far each circle moved
+    fn integrate(&mut self) -> Vec<f32> {
+        self.circles.integrate(
+            self.integrator,
+            self.gravity,
+            self.drag,
+            match self.boundary {
+                Boundary::Wrap => Some(FVec2D::new(self.width, self.height)),
+                Boundary::Open => None,
+            },
+            self.threads,
+        );
+        let circles = &self.circles;
+        circles
+            .positions
+            .iter()
+            .zip(circles.prev_positions.iter())
+            .map(|(&position, &prev_position)| (position - prev_position).length())
+            .collect()
+    }
+
+    ///
+    /// Find every circle touching an edge or another circle.
+    /// Circles closer than they can travel in delta_t are speculative contacts.
+    ///
+    /// Returns the contacts and the number of pairs that were tested
+    ///
+    fn find_contacts(&self, delta_t: f32) -> (Vec<Contact>, usize) {
+        let circles = &self.circles;
+        let lines = &self.lines;
+        // circles close enough to pull on each other count as touching
+        let margin = self.granular.map_or(0.0, |g| g.cohesion_range.max(0.0));
+        let mut bounds = circles.bounds(delta_t);
+        if margin > 0.0 {
+            for aabb in bounds.iter_mut() {
+                *aabb = aabb.expanded(margin);
+            }
+        }
+        let cell_size = circles
+            .radii
+            .iter()
+            .fold(0.0, |size, &r| f32::max(size, r * 2.0));
+        let grid = Grid::new(&bounds, cell_size);
+        let pairs_tested = AtomicUsize::new(0);
+
+        let contacts =
+            parallel::flat_map(&circles.positions, self.threads, |i, &center, contacts| {
+                let mut tested = 0;
+                let radius = circles.radii[i];
+                let speed = circles.velocities[i];
+                let reach = speed.length() * delta_t + margin;
+                // check collisions with edges
+                for edge in lines.iter() {
+                    if !bounds[i].overlaps(&Aabb::from_edge(edge)) {
+                        continue;
+                    }
+                    tested += 1;
+                    let closest_point = edge.closest_point(center);
+                    let distance = (center - closest_point).length();
+                    // colliding with edge
+                    if distance - radius - edge.radius <= reach {
+                        let mut fake_circle =
+                            Circle::new(closest_point, edge.radius, -speed, circles.colors[i]);
+                        //ereduce the mass a little
+                        fake_circle.mass *= 0.8;
+                        contacts.push(Contact::Edge(i, fake_circle));
+                    }
+                }
+                // make sure circles don't run into each other
+                grid.candidates(i, &bounds, |j| {
+                    tested += 1;
+                    let reach = reach + circles.velocities[j].length() * delta_t;
+                    let distance = (circles.positions[j] - center).length();
+                    if distance - radius - circles.radii[j] <= reach {
+                        contacts.push(Contact::Circles(i, j));
+                    }
+                });
+                pairs_tested.fetch_add(tested, Ordering::Relaxed);
+            });
+        (contacts, pairs_tested.into_inner())
+    }
+
+    // Push overlapping circles apart, leaving the allowed overlap
+    fn separate(&mut self, contacts: &[Contact], batches: &Batches) {
+        let contact_slop = self.contact_slop;
+        let solve = |circles: &Bodies, contact: &Contact, moved: &mut Vec<(usize, FVec2D)>| {
+            match *contact {
+                Contact::Circles(i, j) => {
+                    let (mut first, mut second) =
+                        (circles.contact_circle(i), circles.contact_circle(j));
+                    separate_circles(&mut first, &mut second, contact_slop);
+                    moved.push((i, first.center));
+                    moved.push((j, second.center));
+                }
+                Contact::Edge(i, fake_circle) => {
+                    // only push out what goes past the allowed overlap
+                    let center = circles.positions[i];
+                    let offset = center - fake_circle.center;
+                    let overlap =
+                        offset.length() - circles.radii[i] - fake_circle.radius + contact_slop;
+                    if overlap < 0.0 {
+                        moved.push((i, center - offset.unit_vector() * overlap));
+                    }
+                }
+            }
+        };
+
+        for batch in batches.independent.iter() {
+            let circles = &self.circles;
+            let moved = parallel::flat_map(batch, self.threads, |_, &k, moved| {
+                solve(circles, &contacts[k], moved)
+            });
+            for (i, center) in moved {
+                self.circles.positions[i] = center;
+            }
+        }
+        for &k in batches.sequential.iter() {
+            let mut moved = Vec::new();
+            solve(&self.circles, &contacts[k], &mut moved);
+            for (i, center) in moved {
+                self.circles.positions[i] = center;
+            }
+        }
+    }
+
+    // Circles that were pushed back did not travel as far as intended, they get to use the time left.
+    // Circles that did not move at all are done for this sub step.
+    fn consume_time(&mut self, travelled: &[f32]) {
+        let circles = &mut self.circles;
+        let (positions, prev_positions) = (&circles.positions, &circles.prev_positions);
+        parallel::for_each_mut(
+            &mut circles.time_remaining,
+            self.threads,
+            |i, time_remaining| {
+                let actual_distance = (positions[i] - prev_positions[i]).length();
+                let actual_time = if travelled[i] > 0.0 {
+                    *time_remaining * actual_distance / travelled[i]
+                } else {
+                    *time_remaining
+                };
+
+                *time_remaining -= actual_time;
+            },
+        );
+    }
+
+    // handle colliding circle. If they are hit reflect their speed and make them move accordingly
+    // The hit ball hits in the direction tangent of the colision while the hitter moves direction of the normal vector
+    fn respond(&mut self, contacts: &[Contact], batches: &Batches, delta_t: f32) {
+        let resting_speed = self.resting_speed;
+        let granular = self.granular;
+        // (first, second if it is a circle, response, new angular speeds of both in granular mode)
+        let solve = |circles: &Bodies, contact: &Contact| match *contact {
+            Contact::Circles(i, j) => {
+                let (first, second) = (circles.contact_circle(i), circles.contact_circle(j));
+                let response = contact_response(&first, &second, delta_t, resting_speed);
+                match granular {
+                    Some(granular) => {
+                        let (response, spin1, spin2) = granular.contact(&first, &second, response);
+                        (i, Some(j), response, Some((spin1, spin2)))
+                    }
+                    None => (i, Some(j), response, None),
+                }
+            }
+            // second circle is fake ball
+            Contact::Edge(i, fake_circle) => {
+                let circle = circles.contact_circle(i);
+                let response = edge_response(&circle, &fake_circle, delta_t, resting_speed);
+                match granular {
+                    Some(granular) => {
+                        let (response, spin) = granular.edge(&circle, &fake_circle, response);
+                        (i, None, response, Some((spin, 0.0)))
+                    }
+                    None => (i, None, response, None),
+                }
+            }
+        };
+        let apply = |circles: &mut Bodies,
+                     (i, j, response, spins): (
+            usize,
+            Option<usize>,
+            Response,
+            Option<(f32, f32)>,
+        )| {
+            if let Some((spin1, spin2)) = spins {
+                circles.angular_speeds[i] = spin1;
+                if let Some(j) = j {
+                    circles.angular_speeds[j] = spin2;
+                }
+            }
+            match response {
+                Response::None => {}
+                Response::Bounce(speed1, speed2) => {
+                    circles.velocities[i] = speed1;
+                    if let Some(j) = j {
+                        circles.velocities[j] = speed2;
+                    }
+                }
+                // resting circles are done for this sub step, simulating the time
+                // left over would only push them into their contacts again
+                Response::Resting(speed1, speed2) => {
+                    circles.velocities[i] = speed1;
+                    circles.time_remaining[i] = 0.0;
+                    if let Some(j) = j {
+                        circles.velocities[j] = speed2;
+                        circles.time_remaining[j] = 0.0;
+                    }
+                }
+            }
+        };
+
+        for batch in batches.independent.iter() {
+            let circles = &self.circles;
+            let responses = parallel::flat_map(batch, self.threads, |_, &k, responses| {
+                responses.push(solve(circles, &contacts[k]))
+            });
+            for response in responses {
+                apply(&mut self.circles, response);
+            }
+        }
+        for &k in batches.sequential.iter() {
+            let response = solve(&self.circles, &contacts[k]);
+            apply(&mut self.circles, response);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use crate::math::vector::FVec2D;
+use crate::physics::bodies::Bodies;
+use crate::physics::broadphase::Grid;
+use crate::physics::cluster;
+use crate::physics::joint;
+use crate::physics::joint::JointLoad;
+use crate::physics::parallel;
+use crate::physics::query::Aabb;
+use crate::physics::world::{Boundary, Event, World};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// Keeps two circles at a fixed distance from each other, like a link of a rope or chain.
+/// Only enforced by the XPBD solver.
+///     compliance -> inverse stiffness, 0 is perfectly rigid and larger values stretch more
+///     break_force -> force above which the link snaps, None for links that never break
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DistanceConstraint {
+    pub first: usize,
+    pub second: usize,
+    pub rest_length: f32,
+    pub compliance: f32,
+    pub break_force: Option<f32>,
+}
+
+impl DistanceConstraint {
+    pub fn new(first: usize, second: usize, rest_length: f32, compliance: f32) -> Self {
+        Self {
+            first,
+            second,
+            rest_length,
+            compliance,
+            break_force: None,
+        }
+    }
+
+    /// Same link, snapping when it has to pull or push with more than force
+    pub fn with_break_force(self, force: f32) -> Self {
+        Self {
+            break_force: Some(force),
+            ..self
+        }
+    }
+}
+
+/// Largest speed overlapping circles pick up from being pushed apart, on top of their own speed.
+/// Without a limit, circles spawned on top of each other fly apart at the speed
+/// needed to separate them in a single sub step.
+pub const MAX_SEPARATION_SPEED: f32 = 50.0;
+
+// Bodies that may touch during a sub step
+#[derive(Copy, Clone)]
+enum Contact {
+    Circles(usize, usize),
+    // circle index, edge index
+    Edge(usize, usize),
+}
+
+///
+/// Advance the world by delta_t with extended position based dynamics (XPBD).
+///
+/// Each sub step predicts new positions and angles from the speeds, moves and turns the circles
+/// until contacts, distance constraints and joints are satisfied and then derives the speeds from
+/// how far each circle moved and turned since the start of the sub step. Friction then works
+/// on the speeds. Contacts are inelastic, circles stop instead of bouncing.
+/// Circles with an inverse mass of 0 are pinned in place.
+///
+/// At the end of each sub step links and joints pulling harder than their break force snap and
+/// clusters hit harder than their shatter impulse fall apart, all reported in World::events.
+///
+pub(crate) fn step(world: &mut World, delta_t: f32) {
+    let sub_step = delta_t / world.simulation_updates as f32;
+    for _ in 0..world.simulation_updates {
+        predict(world, sub_step);
+        let apart = Apart::new(world);
+        let contacts = find_contacts(world, &apart, sub_step);
+        let mut normal_lambdas = vec![0.0; contacts.len()];
+        let mut lambdas = vec![0.0; world.constraints.len()];
+        let mut loads = vec![JointLoad::default(); world.joints.len()];
+        let mut weld_lambdas: Vec<Vec<f32>> = world
+            .clusters
+            .iter()
+            .map(|cluster| vec![0.0; cluster.welds.len()])
+            .collect();
+        let mut pushes = vec![FVec2D::new(0.0, 0.0); world.circles.len()];
+        for _ in 0..world.max_simulation_steps {
+            solve_contacts(world, &contacts, &mut normal_lambdas, &mut pushes);
+            solve_constraints(
+                &mut world.circles,
+                &world.constraints,
+                &mut lambdas,
+                sub_step,
+            );
+            for (cluster, lambdas) in world.clusters.iter().zip(weld_lambdas.iter_mut()) {
+                solve_constraints(&mut world.circles, &cluster.welds, lambdas, sub_step);
+            }
+            joint::solve_joints(&mut world.circles, &world.joints, &mut loads, sub_step);
+            world.stats.iterations += 1;
+        }
+        update_velocities(world, sub_step);
+        apply_friction(world, &contacts, &normal_lambdas, sub_step);
+        break_joints(world, &lambdas, &loads, sub_step);
+        shatter_clusters(world, &pushes, sub_step);
+    }
+}
+
+// Circles that never touch each other: members of the same cluster and circles joined by a pin
+struct Apart {
+    membership: Vec<Option<usize>>,
+    joined: HashSet<(usize, usize)>,
+}
+
+impl Apart {
+    fn new(world: &World) -> Self {
+        let joined = world
+            .joints
+            .iter()
+            .map(|joint| {
+                let (i, j) = (joint.first, joint.second);
+                (i.min(j), i.max(j))
+            })
+            .collect();
+        Self {
+            membership: cluster::membership(&world.clusters, world.circles.len()),
+            joined,
+        }
+    }
+
+    fn contains(&self, i: usize, j: usize) -> bool {
+        (self.membership[i].is_some() && self.membership[i] == self.membership[j])
+            || self.joined.contains(&(i.min(j), i.max(j)))
+    }
+}
+
+// Remember where every circle starts and move it along its speed after gravity and drag
+fn predict(world: &mut World, delta_t: f32) {
+    let (gravity, drag, integrator) = (world.gravity, world.drag, world.integrator);
+    let circles = &mut world.circles;
+    let bodies = circles
+        .positions
+        .iter_mut()
+        .zip(circles.velocities.iter_mut())
+        .zip(circles.prev_positions.iter_mut())
+        .zip(circles.accelerations.iter_mut())
+        .zip(circles.inverse_masses.iter());
+    for ((((position, velocity), prev_position), acceleration), &inverse_mass) in bodies {
+        *prev_position = *position;
+        if inverse_mass == 0.0 {
+            *velocity = FVec2D::new(0.0, 0.0);
+            continue;
+        }
+        *acceleration = integrator.advance(position, velocity, delta_t, |_, v| -v * drag + gravity);
+    }
+    let turning = circles
+        .angles
+        .iter_mut()
+        .zip(circles.angular_speeds.iter_mut())
+        .zip(circles.prev_angles.iter_mut())
+        .zip(circles.inverse_masses.iter());
+    for (((angle, angular_speed), prev_angle), &inverse_mass) in turning {
+        *prev_angle = *angle;
+        if inverse_mass == 0.0 {
+            *angular_speed = 0.0;
+            continue;
+        }
+        *angle += *angular_speed * delta_t;
+    }
+}
+
+// Circle pairs and circle edge pairs close enough to touch during the sub step
+fn find_contacts(world: &mut World, apart: &Apart, delta_t: f32) -> Vec<Contact> {
+    let circles = &world.circles;
+    let lines = &world.lines;
+    let bounds = circles.bounds(delta_t);
+    let cell_size = circles
+        .radii
+        .iter()
+        .fold(0.0, |size, &r| f32::max(size, r * 2.0));
+    let grid = Grid::new(&bounds, cell_size);
+
+    let pairs_tested = AtomicUsize::new(0);
+    let contacts = parallel::flat_map(&circles.positions, world.threads, |i, &center, contacts| {
+        let mut tested = 0;
+        let radius = circles.radii[i];
+        let reach = circles.velocities[i].length() * delta_t;
+        for (e, edge) in lines.iter().enumerate() {
+            if !bounds[i].overlaps(&Aabb::from_edge(edge)) {
+                continue;
+            }
+            tested += 1;
+            let distance = (center - edge.closest_point(center)).length();
+            if distance - radius - edge.radius <= reach {
+                contacts.push(Contact::Edge(i, e));
+            }
+        }
+        grid.candidates(i, &bounds, |j| {
+            if apart.contains(i, j) {
+                return;
+            }
+            tested += 1;
+            let reach = reach + circles.velocities[j].length() * delta_t;
+            let distance = (circles.positions[j] - center).length();
+            if distance - radius - circles.radii[j] <= reach {
+                contacts.push(Contact::Circles(i, j));
+            }
+        });
+        pairs_tested.fetch_add(tested, Ordering::Relaxed);
+    });
+    world.stats.pairs_tested += pairs_tested.into_inner();
+    world.stats.contacts += contacts.len();
+    contacts
+}
+
+// Push apart every pair that overlaps, in proportion to the inverse masses.
+// Every push is added up in pushes and the size of the push of each contact in normal_lambdas
+fn solve_contacts(
+    world: &mut World,
+    contacts: &[Contact],
+    normal_lambdas: &mut [f32],
+    pushes: &mut [FVec2D],
+) {
+    let circles = &mut world.circles;
+    for (contact, normal_lambda) in contacts.iter().zip(normal_lambdas.iter_mut()) {
+        match *contact {
+            Contact::Circles(i, j) => {
+                let (w1, w2) = (circles.inverse_masses[i], circles.inverse_masses[j]);
+                let offset = circles.positions[i] - circles.positions[j];
+                let distance = offset.length();
+                let penetration = distance - circles.radii[i] - circles.radii[j];
+                if penetration >= 0.0 || w1 + w2 == 0.0 {
+                    continue;
+                }
+                let normal = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    FVec2D::new(1.0, 0.0)
+                };
+                let lambda = -penetration / (w1 + w2);
+                let correction = normal * lambda;
+                *normal_lambda += lambda;
+                circles.positions[i] += correction * w1;
+                circles.positions[j] -= correction * w2;
+                pushes[i] += correction * w1;
+                pushes[j] -= correction * w2;
+            }
+            Contact::Edge(i, e) => {
+                if circles.inverse_masses[i] == 0.0 {
+                    continue;
+                }
+                let edge = &world.lines[e];
+                let position = circles.positions[i];
+                let offset = position - edge.closest_point(position);
+                let distance = offset.length();
+                let penetration = distance - circles.radii[i] - edge.radius;
+                if penetration >= 0.0 || distance == 0.0 {
+                    continue;
+                }
+                circles.positions[i] -= offset / distance * penetration;
+                pushes[i] -= offset / distance * penetration;
+                *normal_lambda -= penetration / circles.inverse_masses[i];
+            }
+        }
+    }
+}
+
+// Move linked circles towards their rest length, softened by the compliance of each link
+fn solve_constraints(
+    circles: &mut Bodies,
+    constraints: &[DistanceConstraint],
+    lambdas: &mut [f32],
+    delta_t: f32,
+) {
+    for (constraint, lambda) in constraints.iter().zip(lambdas.iter_mut()) {
+        let (i, j) = (constraint.first, constraint.second);
+        let (w1, w2) = (circles.inverse_masses[i], circles.inverse_masses[j]);
+        let offset = circles.positions[i] - circles.positions[j];
+        let distance = offset.length();
+        if distance == 0.0 {
+            continue;
+        }
+        let alpha = constraint.compliance / (delta_t * delta_t);
+        if w1 + w2 + alpha == 0.0 {
+            continue;
+        }
+        let error = distance - constraint.rest_length;
+        let delta_lambda = (-error - alpha * *lambda) / (w1 + w2 + alpha);
+        *lambda += delta_lambda;
+        let correction = offset / distance * delta_lambda;
+        circles.positions[i] += correction * w1;
+        circles.positions[j] -= correction * w2;
+    }
+}
+
+// Speeds are whatever moved the circles from where they started the sub step to where they are now,
+// the speed picked up from being pushed apart is limited by MAX_SEPARATION_SPEED on top of the
+// predicted speed. Circles leaving a wrapping world come back around with their starting point.
+fn update_velocities(world: &mut World, delta_t: f32) {
+    let size = FVec2D::new(world.width, world.height);
+    let wrapping = world.boundary == Boundary::Wrap;
+    let circles = &mut world.circles;
+    let bodies = circles
+        .positions
+        .iter_mut()
+        .zip(circles.velocities.iter_mut())
+        .zip(circles.prev_positions.iter_mut());
+    for ((position, velocity), prev_position) in bodies {
+        let pushed = (*position - *prev_position) / delta_t - *velocity;
+        let limit = velocity.length() + MAX_SEPARATION_SPEED;
+        if pushed.squared_length() > limit * limit {
+            *velocity += pushed.unit_vector() * limit;
+        } else {
+            *velocity += pushed;
+        }
+        if wrapping {
+            let wrap = FVec2D::new(
+                wrap_offset(position.x, size.x),
+                wrap_offset(position.y, size.y),
+            );
+            *position += wrap;
+            *prev_position += wrap;
+        }
+    }
+    let turning = circles
+        .angles
+        .iter()
+        .zip(circles.prev_angles.iter())
+        .zip(circles.angular_speeds.iter_mut());
+    for ((angle, prev_angle), angular_speed) in turning {
+        *angular_speed = (angle - prev_angle) / delta_t;
+    }
+}
+
+// Slow down touching bodies sliding over each other where they touch, turning them as well.
+// The friction impulse of a contact is at most friction times its normal impulse
+fn apply_friction(world: &mut World, contacts: &[Contact], normal_lambdas: &[f32], delta_t: f32) {
+    let friction = world.friction;
+    if friction <= 0.0 {
+        return;
+    }
+    let circles = &mut world.circles;
+    for (contact, &lambda) in contacts.iter().zip(normal_lambdas.iter()) {
+        if lambda <= 0.0 {
+            continue;
+        }
+        let limit = friction * lambda / delta_t;
+        match *contact {
+            Contact::Circles(i, j) => {
+                let offset = circles.positions[j] - circles.positions[i];
+                let distance = offset.length();
+                if distance == 0.0 {
+                    continue;
+                }
+                let normal = offset / distance;
+                let (r1, r2) = (normal * circles.radii[i], -normal * circles.radii[j]);
+                let (w1, w2) = (circles.inverse_masses[i], circles.inverse_masses[j]);
+                let (i1, i2) = (circles.inverse_inertia(i), circles.inverse_inertia(j));
+                let relative = circles.velocities[i]
+                    + r1.perpendicular() * circles.angular_speeds[i]
+                    - circles.velocities[j]
+                    - r2.perpendicular() * circles.angular_speeds[j];
+                let weight = w1 + w2 + r1.squared_length() * i1 + r2.squared_length() * i2;
+                let impulse = friction_impulse(relative, normal, weight, limit);
+                circles.velocities[i] += impulse * w1;
+                circles.angular_speeds[i] += FVec2D::cross(r1, impulse) * i1;
+                circles.velocities[j] -= impulse * w2;
+                circles.angular_speeds[j] -= FVec2D::cross(r2, impulse) * i2;
+            }
+            Contact::Edge(i, e) => {
+                let position = circles.positions[i];
+                let offset = world.lines[e].closest_point(position) - position;
+                let distance = offset.length();
+                if distance == 0.0 {
+                    continue;
+                }
+                let normal = offset / distance;
+                let r = normal * circles.radii[i];
+                let (w, inertia) = (circles.inverse_masses[i], circles.inverse_inertia(i));
+                let relative =
+                    circles.velocities[i] + r.perpendicular() * circles.angular_speeds[i];
+                let weight = w + r.squared_length() * inertia;
+                let impulse = friction_impulse(relative, normal, weight, limit);
+                circles.velocities[i] += impulse * w;
+                circles.angular_speeds[i] += FVec2D::cross(r, impulse) * inertia;
+            }
+        }
+    }
+}
+
+// Impulse on the first body stopping the sliding speed at a contact, no stronger than limit
+fn friction_impulse(relative: FVec2D, normal: FVec2D, weight: f32, limit: f32) -> FVec2D {
+    let sliding = relative - normal * FVec2D::dot(relative, normal);
+    let speed = sliding.length();
+    if speed == 0.0 || weight == 0.0 {
+        return FVec2D::new(0.0, 0.0);
+    }
+    -(sliding / speed) * (speed / weight).min(limit)
+}
+
+// Remove the links and joints that held more than their break force or torque during the sub step.
+// The force a link applied follows from its lambda, the impulse it applied over the sub step
+fn break_joints(world: &mut World, lambdas: &[f32], loads: &[JointLoad], delta_t: f32) {
+    let events = &mut world.events;
+    let mut held = loads.iter().map(|load| load.force_and_torque(delta_t));
+    world.joints.retain(|joint| {
+        let (force, torque) = held.next().unwrap_or((0.0, 0.0));
+        let broken = joint.break_force.is_some_and(|limit| force > limit)
+            || joint.break_torque.is_some_and(|limit| torque > limit);
+        if broken {
+            events.push(Event::RevoluteJointBroken {
+                joint: *joint,
+                force,
+                torque,
+            });
+        }
+        !broken
+    });
+    let mut forces = lambdas
+        .iter()
+        .map(|lambda| lambda.abs() / (delta_t * delta_t));
+    world.constraints.retain(|joint| {
+        let force = forces.next().unwrap_or(0.0);
+        match joint.break_force {
+            Some(limit) if force > limit => {
+                events.push(Event::JointBroken {
+                    joint: *joint,
+                    force,
+                });
+                false
+            }
+            _ => true,
+        }
+    });
+}
+
+// Break up the clusters that took a larger impulse from contacts during the sub step
+// than they can stand. The welds only move members around inside a cluster, so the impulse
+// on the whole cluster is the sum of the pushes its members got from contacts
+fn shatter_clusters(world: &mut World, pushes: &[FVec2D], delta_t: f32) {
+    let circles = &world.circles;
+    let events = &mut world.events;
+    world.clusters.retain(|cluster| {
+        let mut impulse = FVec2D::new(0.0, 0.0);
+        for &i in cluster.members.iter() {
+            if circles.inverse_masses[i] > 0.0 {
+                impulse += pushes[i] / (delta_t * circles.inverse_masses[i]);
+            }
+        }
+        let impulse = impulse.length();
+        if impulse > cluster.shatter_impulse {
+            events.push(Event::ClusterShattered {
+                members: cluster.members.clone(),
+                impulse,
+            });
+            false
+        } else {
+            true
+        }
+    });
+}
+
+// How far to move a coordinate to bring it back between 0 and size
+fn wrap_offset(value: f32, size: f32) -> f32 {
+    if value < 0.0 {
+        size
+    } else if value > size {
+        -size
+    } else {
+        0.0
+    }
+}